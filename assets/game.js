@@ -1,11 +1,23 @@
-let id = prompt("Please enter your preferred ID number", Math.floor(Math.random() * 200));
-
 let url = new URL(window.location.href);
-console.log((url.protocol == "http:" ? "ws:" : "wss:") + "//" + url.host + url.pathname + "ws");
-let socket = new WebSocket((url.protocol == "http:" ? "ws:" : "wss:") + "//" + url.host + url.pathname + "ws");
+let room = url.searchParams.get("room");
+// a token from an earlier connection in this tab lets us take our seat back
+let resume_key = "resume-token-" + (room ? room : "");
+let resume_token = sessionStorage.getItem(resume_key);
+let id = resume_token ? 0 : prompt("Please enter your preferred seat number (you sit after players with the same or a smaller number)", Math.floor(Math.random() * 200));
+// leaving the name empty lets the server pick a name and color
+let wanted_name = resume_token ? "" : prompt("Please enter your name (letters and digits only)", "");
+let wanted_color = wanted_name ? prompt("Please enter your color", "#" + Math.floor(Math.random() * 0x1000000).toString(16).toUpperCase().padStart(6, "0")) : "";
+let ws_url = (url.protocol == "http:" ? "ws:" : "wss:") + "//" + url.host + url.pathname + "ws" + (room ? "/" + encodeURIComponent(room) : "");
+console.log(ws_url);
+let socket = new WebSocket(ws_url);
 let bomb_text = { "X": "💣️", "L": "<<", "R": ">>" };
 
 let clickable = [false];
+let player_name = "";
+let joined = false;
+let server_gone = false;
+// the protocol version and capabilities this client understands
+const protocol_line = "2 lobby rounds resume error shutdown bombs stats";
 
 let scoreboard_item = [document.createElement("div"), document.createElement("label"), document.createElement("br")];
 scoreboard_item[0].classList.add("game-players-data");
@@ -15,6 +27,39 @@ scoreboard_item[0].appendChild(document.createElement("label"));
 scoreboard_item[0].children[1].classList.add("game-players-name");
 scoreboard_item[1].classList.add("game-players-score");
 
+function add_bomb_listeners(bomb_row) {
+    [[0, "L3"], [1, "L1"], [3, "R1"], [4, "R2"]].forEach(([button, action]) => {
+        bomb_row.children[button].addEventListener("click", e => {
+            let bomb_index = e.target.parentElement.getAttribute("bomb_index");
+            if (clickable[parseInt(bomb_index)]) {
+                socket.send("move\n" + bomb_index + " " + action)
+                clickable[parseInt(bomb_index)] = false;
+            }
+        });
+    });
+}
+
+// bombs are added at or taken away from the end, new rows are copies of the first one
+function set_bomb_count(count) {
+    var bomb_div = document.getElementById("game-bomb-div");
+    while (bomb_div.children.length > count) {
+        bomb_div.removeChild(bomb_div.lastElementChild);
+        clickable.pop();
+    }
+    for (let i = bomb_div.children.length; i < count; i++) {
+        let bomb_row = bomb_div.children[0].cloneNode(true);
+        bomb_row.setAttribute("bomb_index", i);
+        bomb_row.children[2].innerHTML = "";
+        add_bomb_listeners(bomb_row);
+        bomb_div.appendChild(bomb_row);
+        clickable.push(false);
+    }
+}
+
+document.getElementById("game-lobby-start").addEventListener("click", e => {
+    socket.send("start");
+});
+
 socket.onopen = (event) => {
     socket.onmessage = (msg) => {
         // uses twemoji (https://github.com/twitter/twemoji) as alternative option on mobile
@@ -26,50 +71,22 @@ socket.onopen = (event) => {
         console.log(msg_split);
         switch (msg_split[0]) {
             case "hello":
-                var bomb_div = document.getElementById("game-bomb-div");
-                bomb_count = parseInt(msg_split[1]);
-                for (let i = 1; i < bomb_count; i++) {
-                    clickable.push(false);
-                    bomb_div.appendChild(bomb_div.children[0].cloneNode(true));
-                    bomb_div.children[i].setAttribute("bomb_index", i);
-                }
-                for (let i = 0; i < bomb_count; i++) {
-                    bomb_div.children[i].children[0].addEventListener("click", e => {
-                        let bomb_index = e.target.parentElement.getAttribute("bomb_index");
-                        if (clickable[parseInt(bomb_index)]) {
-                            socket.send("move\n" + bomb_index + " L3")
-                            clickable[parseInt(bomb_index)] = false;
-                        }
-                    });
-                    bomb_div.children[i].children[1].addEventListener("click", e => {
-                        let bomb_index = e.target.parentElement.getAttribute("bomb_index");
-                        if (clickable[parseInt(bomb_index)]) {
-                            socket.send("move\n" + bomb_index + " L1")
-                            clickable[parseInt(bomb_index)] = false;
-                        }
-                    });
-                    bomb_div.children[i].children[3].addEventListener("click", e => {
-                        let bomb_index = e.target.parentElement.getAttribute("bomb_index");
-                        if (clickable[parseInt(bomb_index)]) {
-                            socket.send("move\n" + bomb_index + " R1")
-                            clickable[parseInt(bomb_index)] = false;
-                        }
-                    });
-                    bomb_div.children[i].children[4].addEventListener("click", e => {
-                        let bomb_index = e.target.parentElement.getAttribute("bomb_index");
-                        if (clickable[parseInt(bomb_index)]) {
-                            socket.send("move\n" + bomb_index + " R2")
-                            clickable[parseInt(bomb_index)] = false;
-                        }
-                    });
+                add_bomb_listeners(document.getElementById("game-bomb-div").children[0]);
+                set_bomb_count(parseInt(msg_split[1]));
+                if (resume_token) {
+                    socket.send("resume\n" + resume_token + "\n" + protocol_line);
+                } else {
+                    socket.send("olleh\n" + id + "\n" + protocol_line + (wanted_name ? "\n" + wanted_name + "\n" + wanted_color : ""));
                 }
-                socket.send("olleh\n" + id);
                 break;
             case "name":
                 console.log("player name: " + msg_split[1]);
+                player_name = msg_split[1];
                 document.getElementById("game-player-name").innerText = msg_split[1];
                 console.log("player color: " + msg_split[2]);
                 document.getElementById("game-player-color").style.backgroundColor = msg_split[2];
+                sessionStorage.setItem(resume_key, msg_split[3]);
+                joined = true;
                 break;
             case "status":
                 var bomb_div = document.getElementById("game-bomb-div");
@@ -90,6 +107,85 @@ socket.onopen = (event) => {
                     bomb_div.children[msg_split[1].split(" ")[0]].children[2].classList.add("game-bomb-display-r");
                 }
                 break;
+            case "lobby":
+                let lobby_players_div = document.getElementById("game-lobby-players");
+                let lobby_entries = [];
+                let lobby_count = Math.floor((msg_split.length - 2) / 2);
+                for (let i = 0; i < lobby_count; i++) {
+                    let entry = scoreboard_item[0].cloneNode(true);
+                    entry.children[0].style.backgroundColor = msg_split[2 + 2 * i + 1];
+                    entry.children[1].innerText = msg_split[2 + 2 * i];
+                    lobby_entries.push(entry);
+                }
+                lobby_players_div.replaceChildren(...lobby_entries);
+                document.getElementById("game-lobby-status").innerText =
+                    "Waiting for players... (" + lobby_count + "/" + msg_split[1] + ")";
+                // the first player in the lobby is the host
+                document.getElementById("game-lobby-start").hidden = !(lobby_count > 0 && msg_split[2] == player_name);
+                break;
+            case "begin":
+                document.getElementById("game-lobby").hidden = true;
+                break;
+            case "end":
+                // nobody holds a bomb between rounds
+                var bomb_div = document.getElementById("game-bomb-div");
+                for (let i = 0; i < clickable.length; i++) {
+                    clickable[i] = false;
+                    bomb_div.children[i].children[2].innerHTML = "";
+                }
+                let ranking_entries = [];
+                let ranking_count = Math.floor((msg_split.length - 2) / 3);
+                for (let i = 0; i < ranking_count; i++) {
+                    let entry = scoreboard_item[0].cloneNode(true);
+                    entry.children[0].style.backgroundColor = msg_split[2 + 3 * i + 1];
+                    entry.children[1].innerText = msg_split[2 + 3 * i] + ": " + msg_split[2 + 3 * i + 2];
+                    ranking_entries.push(entry);
+                }
+                document.getElementById("game-lobby-players").replaceChildren(...ranking_entries);
+                document.getElementById("game-lobby-status").innerText =
+                    "Round over! Next round in " + Math.ceil(parseInt(msg_split[1]) / 1000) + "s";
+                document.getElementById("game-lobby-start").hidden = true;
+                document.getElementById("game-lobby").hidden = false;
+                break;
+            case "shutdown":
+                server_gone = true;
+                var bomb_div = document.getElementById("game-bomb-div");
+                for (let i = 0; i < clickable.length; i++) {
+                    clickable[i] = false;
+                    bomb_div.children[i].children[2].innerHTML = "";
+                }
+                let final_entries = [];
+                let final_count = Math.floor((msg_split.length - 1) / 3);
+                for (let i = 0; i < final_count; i++) {
+                    let entry = scoreboard_item[0].cloneNode(true);
+                    entry.children[0].style.backgroundColor = msg_split[1 + 3 * i + 1];
+                    entry.children[1].innerText = msg_split[1 + 3 * i] + ": " + msg_split[1 + 3 * i + 2];
+                    final_entries.push(entry);
+                }
+                document.getElementById("game-lobby-players").replaceChildren(...final_entries);
+                document.getElementById("game-lobby-status").innerText = "The server is shutting down, final scores:";
+                document.getElementById("game-lobby-start").hidden = true;
+                document.getElementById("game-lobby").hidden = false;
+                break;
+            case "bombs":
+                set_bomb_count(parseInt(msg_split[1]));
+                break;
+            case "stats":
+                // sent right after END, adds the passes and the best reaction time to the ranking
+                let ranking_div = document.getElementById("game-lobby-players");
+                for (let i = 1; i < msg_split.length; i++) {
+                    let fields = msg_split[i].split(" ");
+                    for (let entry of ranking_div.children) {
+                        if (entry.children[1].innerText.startsWith(fields[0] + ": ")) {
+                            entry.children[1].innerText += " (" + fields[1] + " passes, best " +
+                                (fields[4] == "-" ? "-" : fields[4] + "ms") + ")";
+                        }
+                    }
+                }
+                break;
+            case "error":
+                console.log("server error " + msg_split[1] + ": " + msg_split[2]);
+                break;
             case "board":
                 let board_count = Math.floor((msg_split.length - 1) / 3);
                 let scoreboard_div = document.getElementById("game-scoreboard");
@@ -107,3 +203,16 @@ socket.onopen = (event) => {
         }
     };
 };
+
+socket.onclose = (event) => {
+    if (server_gone) {
+        // nothing to come back to
+        return;
+    }
+    if (!joined) {
+        // the seat is gone, join as a new player instead
+        sessionStorage.removeItem(resume_key);
+    }
+    // reconnect, resuming the seat if the server still keeps it
+    setTimeout(() => window.location.reload(), 1000);
+};
@@ -0,0 +1,39 @@
+let url = new URL(window.location.href);
+// ?day=YYYY-MM-DD shows an earlier day instead of today
+let day = url.searchParams.get("day");
+let api_url = "api/leaderboard" + (day ? "?day=" + encodeURIComponent(day) : "");
+
+function fill_table(table, entries) {
+    let header = document.createElement("tr");
+    ["#", "Name", "Best", "Total", "Rounds"].forEach(title => {
+        let cell = document.createElement("th");
+        cell.innerText = title;
+        header.appendChild(cell);
+    });
+    let rows = [header];
+    entries.forEach((entry, rank) => {
+        let row = document.createElement("tr");
+        [rank + 1, entry.name, entry.best_score, entry.total_score, entry.rounds].forEach(value => {
+            let cell = document.createElement("td");
+            cell.innerText = value;
+            row.appendChild(cell);
+        });
+        rows.push(row);
+    });
+    table.replaceChildren(...rows);
+}
+
+function refresh() {
+    fetch(api_url)
+        .then(response => response.json())
+        .then(leaderboard => {
+            fill_table(document.getElementById("leaderboard-all-time"), leaderboard.all_time);
+            fill_table(document.getElementById("leaderboard-daily"), leaderboard.daily);
+            document.getElementById("leaderboard-daily-title").innerText = day ? leaderboard.day : "Today (" + leaderboard.day + ")";
+        })
+        .catch(err => console.log("failed loading the leaderboard: " + err));
+}
+
+refresh();
+// scores come in at the end of every round
+setInterval(refresh, 10000);
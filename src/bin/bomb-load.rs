@@ -0,0 +1,264 @@
+// Load generator, connects lots of simulated players to one game and reports how the
+// server keeps up
+
+use futures_util::StreamExt;
+use multi_bomb_test::bot::BotBehavior;
+use multi_bomb_test::client::{connect, ClientOptions};
+use multi_bomb_test::packet::{BombIndex, BombMoveAction, BombPosition, ServerPacket, WireFormat};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, clap::Parser)]
+#[command(
+    version,
+    about = "Simulates many players to load test a multi-bomb-test server"
+)]
+struct LoadArgs {
+    /// Websocket address of the game, e.g. ws://127.0.0.1:3000/ws/load
+    #[arg(default_value = "ws://127.0.0.1:3000/ws/load")]
+    url: String,
+    /// Amount of simulated players
+    #[arg(short, long, default_value_t = 100)]
+    players: u32,
+    /// Seconds to keep the players connected
+    #[arg(short, long, default_value_t = 30)]
+    duration_secs: u64,
+    /// Milliseconds between opening two connections
+    #[arg(long, default_value_t = 10)]
+    ramp_ms: u64,
+    /// Average milliseconds a player waits before passing a bomb
+    #[arg(long, default_value_t = 300)]
+    latency_mean_ms: u64,
+    /// Standard deviation of the time a player waits before passing a bomb
+    #[arg(long, default_value_t = 100)]
+    latency_std_dev_ms: u64,
+    /// Use the JSON wire format instead of the text one
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Default)]
+struct LoadStats {
+    connected: u32,
+    handshake_failures: Vec<String>,
+    dropped: Vec<String>,
+    moves_sent: u64,
+    statuses_received: u64,
+    // from sending a MOVE to the new holder getting the STATUS for it
+    status_latencies: Vec<Duration>,
+}
+
+// when the last MOVE of every bomb was sent, shared by all players of the game
+type MoveTimes = Arc<Mutex<BTreeMap<BombIndex, Instant>>>;
+
+const ACTIONS: [BombMoveAction; 4] = [
+    BombMoveAction::L3,
+    BombMoveAction::L1,
+    BombMoveAction::R1,
+    BombMoveAction::R2,
+];
+
+async fn run_player(
+    index: u32,
+    url: String,
+    wire_format: WireFormat,
+    behavior: BotBehavior,
+    deadline: Instant,
+    stats: Arc<Mutex<LoadStats>>,
+    move_times: MoveTimes,
+) {
+    let options = ClientOptions {
+        preferred_id: index,
+        player_data: Some((
+            format!("Load{}", index),
+            format!("#{:06X}", index.wrapping_mul(2654435) % 0x1000000),
+        )),
+        wire_format,
+        ..Default::default()
+    };
+    let client = match connect(&url, options).await {
+        Ok(client) => client,
+        Err(err) => {
+            let mut stats = stats.lock().unwrap();
+            stats
+                .handshake_failures
+                .push(format!("player {}: {}", index, err));
+            return;
+        }
+    };
+    stats.lock().unwrap().connected += 1;
+    let (session, mut sender, mut packets) = client.split();
+    let mut pending = tokio::task::JoinSet::new();
+
+    let dropped = loop {
+        tokio::select! {
+            packet = packets.next() => match packet {
+                Some(Ok(ServerPacket::PacketSTATUS(bomb_index, position))) => {
+                    let sent_at = if position == BombPosition::X {
+                        move_times.lock().unwrap().remove(&bomb_index)
+                    } else {
+                        None
+                    };
+                    {
+                        let mut stats = stats.lock().unwrap();
+                        stats.statuses_received += 1;
+                        if let Some(sent_at) = sent_at {
+                            stats.status_latencies.push(sent_at.elapsed());
+                        }
+                    }
+
+                    if position == BombPosition::X {
+                        let reaction_time = behavior.reaction_time(&mut rand::thread_rng());
+                        pending.spawn(async move {
+                            tokio::time::sleep(reaction_time).await;
+                            bomb_index
+                        });
+                    }
+                }
+                // the first player in the lobby starts the game
+                Some(Ok(ServerPacket::PacketLOBBY(_, players))) => {
+                    if players.first().is_some_and(|(name, _)| *name == session.player_name) {
+                        let _ = sender.start().await;
+                    }
+                }
+                Some(Ok(ServerPacket::PacketEND(..))) => pending.abort_all(),
+                Some(Ok(ServerPacket::PacketSHUTDOWN(_))) => break Some("server shut down".to_string()),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => break Some(err.to_string()),
+                None => break Some("connection closed by server".to_string()),
+            },
+            Some(Ok(bomb_index)) = pending.join_next(), if !pending.is_empty() => {
+                let action = ACTIONS[rand::random::<usize>() % ACTIONS.len()];
+                move_times.lock().unwrap().insert(bomb_index, Instant::now());
+                if let Err(err) = sender.send_move(bomb_index, action).await {
+                    break Some(err.to_string());
+                }
+                stats.lock().unwrap().moves_sent += 1;
+            }
+            _ = tokio::time::sleep_until(deadline) => break None,
+        }
+    };
+
+    stats.lock().unwrap().connected -= 1;
+    match dropped {
+        Some(reason) => {
+            let mut stats = stats.lock().unwrap();
+            stats.dropped.push(format!("player {}: {}", index, reason));
+        }
+        None => {
+            let _ = sender.close().await;
+        }
+    }
+}
+
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    sorted[(sorted.len() - 1) * percent / 100]
+}
+
+fn print_failures(title: &str, failures: &[String]) {
+    println!("{}: {}", title, failures.len());
+    // the same reason tends to repeat for every player
+    for failure in failures.iter().take(10) {
+        println!("  {}", failure);
+    }
+    if failures.len() > 10 {
+        println!("  ... and {} more", failures.len() - 10);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = <LoadArgs as clap::Parser>::parse();
+    let wire_format = if args.json {
+        WireFormat::Json
+    } else {
+        WireFormat::Text
+    };
+    let behavior = BotBehavior {
+        reaction_mean_ms: args.latency_mean_ms,
+        reaction_std_dev_ms: args.latency_std_dev_ms,
+    };
+
+    let stats = Arc::new(Mutex::new(LoadStats::default()));
+    let move_times = MoveTimes::default();
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration_secs);
+
+    println!(
+        "connecting {} players to {} for {}s",
+        args.players, args.url, args.duration_secs
+    );
+
+    // spawn the players from their own task so progress gets reported while ramping up
+    let spawner = {
+        let stats = stats.clone();
+        tokio::spawn(async move {
+            let mut players = tokio::task::JoinSet::new();
+            for index in 0..args.players {
+                players.spawn(run_player(
+                    index,
+                    args.url.clone(),
+                    wire_format,
+                    behavior.clone(),
+                    deadline,
+                    stats.clone(),
+                    move_times.clone(),
+                ));
+                tokio::time::sleep(Duration::from_millis(args.ramp_ms)).await;
+            }
+            while players.join_next().await.is_some() {}
+        })
+    };
+    tokio::pin!(spawner);
+
+    let mut report = tokio::time::interval(Duration::from_secs(1));
+    let (mut last_moves, mut last_statuses) = (0, 0);
+    loop {
+        tokio::select! {
+            _ = &mut spawner => break,
+            _ = report.tick() => {
+                let stats = stats.lock().unwrap();
+                println!(
+                    "{:>4}s connected {:>5}  moves/s {:>6}  statuses/s {:>8}  dropped {}",
+                    started.elapsed().as_secs(),
+                    stats.connected,
+                    stats.moves_sent - last_moves,
+                    stats.statuses_received - last_statuses,
+                    stats.dropped.len(),
+                );
+                (last_moves, last_statuses) = (stats.moves_sent, stats.statuses_received);
+            }
+        }
+    }
+
+    let mut stats = stats.lock().unwrap();
+    let elapsed = started.elapsed().as_secs_f64();
+    println!();
+    println!(
+        "moves sent: {} ({:.1}/s)",
+        stats.moves_sent,
+        stats.moves_sent as f64 / elapsed
+    );
+    println!(
+        "statuses received: {} ({:.1}/s)",
+        stats.statuses_received,
+        stats.statuses_received as f64 / elapsed
+    );
+    stats.status_latencies.sort();
+    if stats.status_latencies.is_empty() {
+        println!("status latency: no bombs were passed");
+    } else {
+        let latencies = &stats.status_latencies;
+        println!(
+            "status latency: p50 {:?}  p90 {:?}  p99 {:?}  max {:?}",
+            percentile(latencies, 50),
+            percentile(latencies, 90),
+            percentile(latencies, 99),
+            latencies[latencies.len() - 1],
+        );
+    }
+    print_failures("failed handshakes", &stats.handshake_failures);
+    print_failures("dropped connections", &stats.dropped);
+}
@@ -0,0 +1,164 @@
+// Replays the event log of a game, printing every event with the bomb positions and the
+// scoreboard it leads to
+
+use multi_bomb_test::events::{GameEvent, Replay, TimedEvent};
+use std::io::BufRead;
+
+#[derive(Debug, clap::Parser)]
+#[command(
+    version,
+    about = "Replays a multi-bomb-test event log and rebuilds the game over time"
+)]
+struct ReplayArgs {
+    /// Event log written by the server with --event-log-dir
+    log: std::path::PathBuf,
+    /// Play the log back in real time sped up by this factor, 0 prints it all at once
+    #[arg(short, long, default_value_t = 0.0)]
+    speed: f64,
+    /// Stop at this many milliseconds into the game
+    #[arg(short, long)]
+    until_ms: Option<u64>,
+    /// Only print the state at the end
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+fn describe(event: &GameEvent, replay: &Replay) -> String {
+    match event {
+        GameEvent::Start {
+            room,
+            unix_ms,
+            seed,
+        } => format!(
+            "game in room {} started at {} with seed {}",
+            room, unix_ms, seed
+        ),
+        GameEvent::Join {
+            player,
+            name,
+            color,
+            bot,
+        } => format!(
+            "{} ({}{}) joined as {}",
+            name,
+            color,
+            if *bot { ", bot" } else { "" },
+            player
+        ),
+        GameEvent::Leave { player } => format!("{} left", replay.name(*player)),
+        GameEvent::RoundStart { holders } => format!("round started with {} bombs", holders.len()),
+        GameEvent::RoundEnd => "round ended".to_string(),
+        GameEvent::Pass {
+            bomb,
+            from,
+            to,
+            action,
+            ms,
+        } => format!(
+            "{} passed bomb {} to {} ({:?} after {}ms)",
+            replay.name(*from),
+            bomb,
+            replay.name(*to),
+            action,
+            ms
+        ),
+        GameEvent::Timeout {
+            bomb,
+            player,
+            to,
+            ms,
+        } => format!(
+            "{} held bomb {} for {}ms, it went on to {}",
+            replay.name(*player),
+            bomb,
+            ms,
+            replay.name(*to)
+        ),
+        GameEvent::ScoreChange {
+            player,
+            change,
+            score,
+        } => format!("{} {:+} points ({})", replay.name(*player), change, score),
+        GameEvent::BombPlaced { bomb, holder } => {
+            format!("bomb {} went to {}", bomb, replay.name(*holder))
+        }
+        GameEvent::BombCount { count } => format!("bomb count changed to {}", count),
+        GameEvent::ScoresReset => "scores were reset".to_string(),
+    }
+}
+
+fn print_bombs(replay: &Replay) {
+    let holders = replay
+        .bombs
+        .iter()
+        .map(|holder| replay.name(*holder))
+        .collect::<Vec<_>>();
+    println!("            bombs: {}", holders.join(" "));
+}
+
+fn print_scoreboard(replay: &Replay) {
+    for (player, score) in replay.scoreboard() {
+        println!("            {:>32} {}", replay.name(player), score);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = <ReplayArgs as clap::Parser>::parse();
+    let file = match std::fs::File::open(&args.log) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("failed opening {}: {}", args.log.display(), err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut replay = Replay::new();
+    let mut last_at_ms = 0;
+    for (line_number, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("failed reading {}: {}", args.log.display(), err);
+                std::process::exit(1);
+            }
+        };
+        // the server can stop in the middle of writing a line
+        let timed = match serde_json::from_str::<TimedEvent>(&line) {
+            Ok(timed) => timed,
+            Err(err) => {
+                eprintln!("line {} is not an event: {}", line_number + 1, err);
+                break;
+            }
+        };
+        if args.until_ms.is_some_and(|until_ms| timed.at_ms > until_ms) {
+            break;
+        }
+        if args.speed > 0.0 {
+            let wait = timed.at_ms.saturating_sub(last_at_ms) as f64 / args.speed;
+            tokio::time::sleep(std::time::Duration::from_secs_f64(wait / 1000.0)).await;
+        }
+        last_at_ms = timed.at_ms;
+
+        // names are looked up before the event, so players who leave still have one
+        let description = describe(&timed.event, &replay);
+        replay.apply(&timed.event);
+        if args.quiet {
+            continue;
+        }
+        println!("[{:>8.3}s] {}", timed.at_ms as f64 / 1000.0, description);
+        match timed.event {
+            GameEvent::RoundStart { .. }
+            | GameEvent::Pass { .. }
+            | GameEvent::Timeout { .. }
+            | GameEvent::BombPlaced { .. }
+            | GameEvent::BombCount { .. } => print_bombs(&replay),
+            GameEvent::RoundEnd | GameEvent::ScoresReset => print_scoreboard(&replay),
+            _ => {}
+        }
+    }
+
+    println!("[{:>8.3}s] final state", last_at_ms as f64 / 1000.0);
+    print_bombs(&replay);
+    print_scoreboard(&replay);
+}
@@ -0,0 +1,438 @@
+// Terminal client, for playing from a terminal or over SSH
+
+use crossterm::{cursor, event, queue, style, terminal};
+use futures_util::StreamExt;
+use multi_bomb_test::client::{connect, ClientOptions, Session};
+use multi_bomb_test::packet::{
+    BombMoveAction, BombPosition, GameScoareboard, PlayerColor, PlayerCount, PlayerData,
+    PlayerName, PlayerStats, PreferredID, ServerPacket, WireFormat,
+};
+use std::io::Write;
+
+#[derive(Debug, clap::Parser)]
+#[command(
+    version,
+    about = "Terminal client for the multiplayer bomb passing game"
+)]
+struct TuiArgs {
+    /// Websocket address of the game, e.g. ws://127.0.0.1:3000/ws/party
+    #[arg(default_value = "ws://127.0.0.1:3000/ws")]
+    url: String,
+    /// Preferred seat, players sit after everyone with the same or a smaller one
+    #[arg(long, default_value_t = 0)]
+    id: PreferredID,
+    /// Player name (letters and digits only), picked by the server if not given
+    #[arg(long, requires = "color")]
+    name: Option<PlayerName>,
+    /// Player color like #FF8000
+    #[arg(long, requires = "name")]
+    color: Option<PlayerColor>,
+    /// Use the JSON wire format instead of the text one
+    #[arg(long)]
+    json: bool,
+}
+
+// keys passing the selected bomb, in the same order as the buttons of a row
+const ACTION_KEYS: [(char, BombMoveAction); 4] = [
+    ('a', BombMoveAction::L3),
+    ('s', BombMoveAction::L1),
+    ('d', BombMoveAction::R1),
+    ('f', BombMoveAction::R2),
+];
+
+const ROW_BORDER: &str = "+-------+-------+---------------+-------+-------+";
+
+enum Phase {
+    Lobby(PlayerCount, Vec<PlayerData>),
+    Playing,
+    RoundOver(u32, GameScoareboard),
+    Shutdown(GameScoareboard),
+}
+
+struct GameView {
+    session: Session,
+    bombs: Vec<Option<BombPosition>>,
+    selected: usize,
+    scoreboard: GameScoareboard,
+    // our own stats from the last STATS packet
+    stats: Option<PlayerStats>,
+    phase: Phase,
+    message: String,
+    closed: bool,
+}
+
+impl GameView {
+    fn new(session: Session) -> Self {
+        GameView {
+            bombs: vec![None; session.bomb_count as usize],
+            session,
+            selected: 0,
+            scoreboard: String::new(),
+            stats: None,
+            // servers without the lobby capability start right away
+            phase: Phase::Playing,
+            message: String::new(),
+            closed: false,
+        }
+    }
+
+    fn is_host(&self) -> bool {
+        match &self.phase {
+            Phase::Lobby(_, players) => players
+                .first()
+                .is_some_and(|(name, _)| *name == self.session.player_name),
+            _ => false,
+        }
+    }
+
+    fn handle_packet(&mut self, packet: ServerPacket) {
+        match packet {
+            ServerPacket::PacketSTATUS(bomb_index, position) => {
+                let bomb_index = bomb_index as usize;
+                if bomb_index >= self.bombs.len() {
+                    return;
+                }
+                // jump to a bomb we just received unless the selected one needs passing too
+                if position == BombPosition::X && self.bombs[self.selected] != Some(BombPosition::X)
+                {
+                    self.selected = bomb_index;
+                }
+                self.bombs[bomb_index] = Some(position);
+            }
+            ServerPacket::PacketBOARD(board) => self.scoreboard = board,
+            ServerPacket::PacketLOBBY(min_players, players) => {
+                self.phase = Phase::Lobby(min_players, players)
+            }
+            ServerPacket::PacketBEGIN => self.phase = Phase::Playing,
+            ServerPacket::PacketEND(cooldown, ranking) => {
+                // nobody holds a bomb between rounds
+                self.bombs.iter_mut().for_each(|bomb| *bomb = None);
+                self.phase = Phase::RoundOver(cooldown, ranking);
+            }
+            ServerPacket::PacketERROR(code, reason) => {
+                self.message = format!("server error {}: {}", code as u32, reason)
+            }
+            ServerPacket::PacketSHUTDOWN(board) => {
+                self.bombs.iter_mut().for_each(|bomb| *bomb = None);
+                self.phase = Phase::Shutdown(board);
+            }
+            ServerPacket::PacketSTATS(players_stats) => {
+                self.stats = players_stats
+                    .into_iter()
+                    .find(|(name, _)| *name == self.session.player_name)
+                    .map(|(_, stats)| stats);
+            }
+            // added bombs get a STATUS right after this
+            ServerPacket::PacketBOMBS(bomb_count) => {
+                self.bombs.resize(bomb_count as usize, None);
+                self.selected = self.selected.min(self.bombs.len().saturating_sub(1));
+            }
+            // views are only sent to spectators
+            ServerPacket::PacketHELLO(..)
+            | ServerPacket::PacketNAME(..)
+            | ServerPacket::PacketVIEW(..) => {}
+        }
+    }
+
+    // the move to send for a key press, if the selected bomb can be passed
+    fn take_move(&mut self, key: char) -> Option<(usize, BombMoveAction)> {
+        let (_, action) = ACTION_KEYS.iter().find(|(k, _)| *k == key)?;
+        if self.bombs[self.selected] != Some(BombPosition::X) {
+            return None;
+        }
+        // don't pass the same bomb twice before the server answers
+        self.bombs[self.selected] = None;
+        Some((self.selected, *action))
+    }
+}
+
+fn parse_color(color: &str) -> style::Color {
+    match u32::from_str_radix(color.trim_start_matches('#'), 16) {
+        Ok(rgb) => style::Color::Rgb {
+            r: (rgb >> 16) as u8,
+            g: (rgb >> 8) as u8,
+            b: rgb as u8,
+        },
+        Err(_) => style::Color::Reset,
+    }
+}
+
+// lines of (name, color, score) from a text scoreboard
+fn board_entries(board: &str) -> Vec<(&str, &str, &str)> {
+    let lines: Vec<&str> = board.lines().collect();
+    lines
+        .chunks_exact(3)
+        .map(|entry| (entry[0], entry[1], entry[2]))
+        .collect()
+}
+
+fn draw_player(
+    out: &mut impl Write,
+    row: u16,
+    name: &str,
+    color: &str,
+    extra: &str,
+) -> std::io::Result<()> {
+    queue!(
+        out,
+        cursor::MoveTo(2, row),
+        style::SetForegroundColor(parse_color(color)),
+        style::Print("██ "),
+        style::ResetColor,
+        style::Print(format!("{} {}", name, extra)),
+    )
+}
+
+fn draw(out: &mut impl Write, view: &GameView) -> std::io::Result<()> {
+    queue!(
+        out,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        style::SetForegroundColor(parse_color(&view.session.player_color)),
+        style::Print("██ "),
+        style::ResetColor,
+        style::Print(format!(
+            "{}    ↑/↓ or 1-9: pick bomb   a s d f: pass   enter: start   t: stats   q: quit",
+            view.session.player_name
+        )),
+        cursor::MoveTo(4, 2),
+        style::Print("    a       s                       d       f"),
+    )?;
+
+    let mut row = 3;
+    for (bomb_index, bomb) in view.bombs.iter().enumerate() {
+        let marker = if bomb_index == view.selected {
+            '>'
+        } else {
+            ' '
+        };
+        let center = match bomb {
+            Some(BombPosition::X) => ">!!!<",
+            Some(BombPosition::L) => "<< BOMB",
+            Some(BombPosition::R) => "BOMB >>",
+            None => "",
+        };
+        queue!(
+            out,
+            cursor::MoveTo(4, row),
+            style::Print(ROW_BORDER),
+            cursor::MoveTo(0, row + 1),
+            style::Print(format!("{}{:<2} |  < 3  |  < 1  |", marker, bomb_index + 1)),
+        )?;
+        if *bomb == Some(BombPosition::X) {
+            queue!(out, style::SetForegroundColor(style::Color::Red))?;
+        }
+        queue!(
+            out,
+            style::Print(format!("{:^15}", center)),
+            style::ResetColor,
+            style::Print("|  1 >  |  2 >  |"),
+        )?;
+        row += 2;
+    }
+    queue!(out, cursor::MoveTo(4, row), style::Print(ROW_BORDER))?;
+    row += 2;
+
+    match &view.phase {
+        Phase::Lobby(min_players, players) => {
+            queue!(
+                out,
+                cursor::MoveTo(0, row),
+                style::Print(format!(
+                    "Waiting for players... ({}/{}){}",
+                    players.len(),
+                    min_players,
+                    if view.is_host() {
+                        ", press enter to start"
+                    } else {
+                        ""
+                    }
+                )),
+            )?;
+            for (name, color) in players {
+                row += 1;
+                draw_player(out, row, name, color, "")?;
+            }
+        }
+        Phase::Playing => {
+            queue!(out, cursor::MoveTo(0, row), style::Print("Scoreboard"))?;
+            for (name, color, score) in board_entries(&view.scoreboard) {
+                row += 1;
+                draw_player(out, row, name, color, score)?;
+            }
+        }
+        Phase::RoundOver(cooldown, ranking) => {
+            queue!(
+                out,
+                cursor::MoveTo(0, row),
+                style::Print(format!(
+                    "Round over! Next round in {}s",
+                    cooldown.div_ceil(1000)
+                )),
+            )?;
+            for (name, color, score) in board_entries(ranking) {
+                row += 1;
+                draw_player(out, row, name, color, score)?;
+            }
+        }
+        Phase::Shutdown(board) => {
+            queue!(
+                out,
+                cursor::MoveTo(0, row),
+                style::Print("The server is shutting down, final scores:"),
+            )?;
+            for (name, color, score) in board_entries(board) {
+                row += 1;
+                draw_player(out, row, name, color, score)?;
+            }
+        }
+    }
+
+    if let Some(stats) = &view.stats {
+        row += 2;
+        let optional =
+            |time: Option<u32>| time.map_or("-".to_string(), |time| format!("{}ms", time));
+        queue!(
+            out,
+            cursor::MoveTo(0, row),
+            style::Print(format!(
+                "passes {}  timeouts {}  received {}  best {}  mean {}  median {}  longest hold {}ms",
+                stats.passes,
+                stats.timeouts,
+                stats.bombs_received,
+                optional(stats.best_reaction_ms),
+                optional(stats.mean_reaction_ms),
+                optional(stats.median_reaction_ms),
+                stats.longest_hold_ms
+            )),
+        )?;
+    }
+
+    row += 2;
+    queue!(out, cursor::MoveTo(0, row), style::Print(&view.message))?;
+    if view.closed {
+        queue!(
+            out,
+            cursor::MoveTo(0, row + 1),
+            style::Print("Connection closed, press q to quit"),
+        )?;
+    }
+    out.flush()
+}
+
+// puts the terminal back to normal however the client exits
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        crossterm::execute!(
+            std::io::stdout(),
+            terminal::EnterAlternateScreen,
+            cursor::Hide
+        )?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(
+            std::io::stdout(),
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = <TuiArgs as clap::Parser>::parse();
+    let options = ClientOptions {
+        preferred_id: args.id,
+        player_data: args.name.zip(args.color),
+        wire_format: if args.json {
+            WireFormat::Json
+        } else {
+            WireFormat::Text
+        },
+        ..Default::default()
+    };
+
+    let client = match connect(&args.url, options).await {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    let (session, mut sender, mut packets) = client.split();
+    let mut view = GameView::new(session);
+
+    let guard = match TerminalGuard::enter() {
+        Ok(guard) => guard,
+        Err(err) => {
+            eprintln!("failed setting up the terminal: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let mut out = std::io::stdout();
+    let mut keys = event::EventStream::new();
+
+    loop {
+        if let Err(err) = draw(&mut out, &view) {
+            drop(guard);
+            eprintln!("failed drawing: {}", err);
+            std::process::exit(1);
+        }
+
+        tokio::select! {
+            packet = packets.next(), if !view.closed => match packet {
+                Some(Ok(packet)) => view.handle_packet(packet),
+                Some(Err(err)) => view.message = err.to_string(),
+                None => view.closed = true,
+            },
+            key = keys.next() => {
+                let key = match key {
+                    Some(Ok(event::Event::Key(key))) if key.kind != event::KeyEventKind::Release => key,
+                    // resizes and such only need a redraw
+                    Some(Ok(_)) => continue,
+                    Some(Err(_)) | None => break,
+                };
+                let result = match key.code {
+                    event::KeyCode::Char('q') | event::KeyCode::Esc => break,
+                    event::KeyCode::Char('c') if key.modifiers.contains(event::KeyModifiers::CONTROL) => break,
+                    event::KeyCode::Up => {
+                        view.selected = view.selected.saturating_sub(1);
+                        Ok(())
+                    }
+                    event::KeyCode::Down => {
+                        view.selected = (view.selected + 1).min(view.bombs.len() - 1);
+                        Ok(())
+                    }
+                    event::KeyCode::Char(digit @ '1'..='9') => {
+                        let bomb_index = digit as usize - '1' as usize;
+                        if bomb_index < view.bombs.len() {
+                            view.selected = bomb_index;
+                        }
+                        Ok(())
+                    }
+                    event::KeyCode::Enter if view.is_host() && !view.closed => sender.start().await,
+                    event::KeyCode::Char('t') if !view.closed => sender.request_stats().await,
+                    event::KeyCode::Char(key) if !view.closed => match view.take_move(key) {
+                        Some((bomb_index, action)) => sender.send_move(bomb_index as u32, action).await,
+                        None => Ok(()),
+                    },
+                    _ => Ok(()),
+                };
+                if let Err(err) = result {
+                    view.message = err.to_string();
+                }
+            }
+        }
+    }
+
+    let _ = sender.close().await;
+    drop(guard);
+}
@@ -0,0 +1,62 @@
+// Behavior of the computer players that can be added to a game
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BotDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl std::fmt::Display for BotDifficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                BotDifficulty::Easy => "easy",
+                BotDifficulty::Normal => "normal",
+                BotDifficulty::Hard => "hard",
+            }
+        )
+    }
+}
+
+// bots never answer faster than this, no matter how lucky the roll is
+const MIN_REACTION_MS: f64 = 150.0;
+
+// Reaction times of a bot follow a normal distribution (cut off at MIN_REACTION_MS)
+#[derive(Debug, Clone)]
+pub struct BotBehavior {
+    pub reaction_mean_ms: u64,
+    pub reaction_std_dev_ms: u64,
+}
+
+impl BotBehavior {
+    pub fn new(
+        difficulty: BotDifficulty,
+        reaction_mean_ms: Option<u64>,
+        reaction_std_dev_ms: Option<u64>,
+    ) -> Self {
+        let (default_mean_ms, default_std_dev_ms) = match difficulty {
+            BotDifficulty::Easy => (2500, 1200),
+            BotDifficulty::Normal => (1200, 500),
+            BotDifficulty::Hard => (450, 150),
+        };
+        BotBehavior {
+            reaction_mean_ms: reaction_mean_ms.unwrap_or(default_mean_ms),
+            reaction_std_dev_ms: reaction_std_dev_ms.unwrap_or(default_std_dev_ms),
+        }
+    }
+
+    pub fn reaction_time<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> std::time::Duration {
+        let distribution = rand_distr::Normal::new(
+            self.reaction_mean_ms as f64,
+            self.reaction_std_dev_ms as f64,
+        )
+        .unwrap();
+        let reaction_ms = rand_distr::Distribution::sample(&distribution, rng).max(MIN_REACTION_MS);
+        std::time::Duration::from_millis(reaction_ms as u64)
+    }
+}
@@ -0,0 +1,288 @@
+// Async client for the bomb protocol, connects to a server and turns the websocket
+// messages into ServerPackets
+
+use crate::packet::{
+    BombCount, BombIndex, BombMoveAction, ClientPacket, ErrorCode, Negotiated, PacketError,
+    PlayerColor, PlayerData, PlayerName, PreferredID, ProtocolSupport, ResumeToken, ServerPacket,
+    WireFormat, PROTOCOL_VERSION, SERVER_CAPABILITIES,
+};
+use futures_util::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http, Message};
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+#[derive(Debug)]
+pub enum ClientError {
+    WebSocket(tokio_tungstenite::tungstenite::Error),
+    Packet(PacketError),
+    // the server sent an ERROR packet instead of letting us join
+    Rejected(ErrorCode, String),
+    Handshake(&'static str),
+    Closed,
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WebSocket(err) => write!(f, "websocket error: {}", err),
+            Self::Packet(err) => write!(f, "bad packet from server: {}", err),
+            Self::Rejected(code, reason) => {
+                write!(f, "rejected by server ({}): {}", *code as u32, reason)
+            }
+            Self::Handshake(reason) => write!(f, "handshake failed: {}", reason),
+            Self::Closed => write!(f, "connection closed by server"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<tokio_tungstenite::tungstenite::Error> for ClientError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        ClientError::WebSocket(err)
+    }
+}
+
+impl From<PacketError> for ClientError {
+    fn from(err: PacketError) -> Self {
+        ClientError::Packet(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    // where the player wants to sit, the server picks the player ID itself
+    pub preferred_id: PreferredID,
+    // None lets the server pick a name and color
+    pub player_data: Option<PlayerData>,
+    // take back a seat from an earlier connection instead of joining as a new player
+    pub resume_token: Option<ResumeToken>,
+    // the server falls back to the text format if it doesn't know the asked one
+    pub wire_format: WireFormat,
+    pub protocol: ProtocolSupport,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            preferred_id: 0,
+            player_data: None,
+            resume_token: None,
+            wire_format: WireFormat::Text,
+            protocol: (
+                PROTOCOL_VERSION,
+                SERVER_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+            ),
+        }
+    }
+}
+
+// What the handshake told us about the game and ourselves
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub bomb_count: BombCount,
+    pub player_name: PlayerName,
+    pub player_color: PlayerColor,
+    // only handed out when both sides support resuming
+    pub resume_token: Option<ResumeToken>,
+    pub negotiated: Negotiated,
+    pub wire_format: WireFormat,
+}
+
+pub struct PacketSender {
+    sink: futures_util::stream::SplitSink<Socket, Message>,
+    wire_format: WireFormat,
+}
+
+impl PacketSender {
+    pub async fn send(&mut self, packet: &ClientPacket) -> Result<(), ClientError> {
+        let message = Message::Text(self.wire_format.encode_client(packet));
+        Ok(self.sink.send(message).await?)
+    }
+
+    pub async fn send_move(
+        &mut self,
+        bomb_index: BombIndex,
+        action: BombMoveAction,
+    ) -> Result<(), ClientError> {
+        self.send(&ClientPacket::PacketMOVE(bomb_index, action))
+            .await
+    }
+
+    pub async fn start(&mut self) -> Result<(), ClientError> {
+        self.send(&ClientPacket::PacketSTART).await
+    }
+
+    // the server answers with a STATS packet if the stats capability was negotiated
+    pub async fn request_stats(&mut self) -> Result<(), ClientError> {
+        self.send(&ClientPacket::PacketSTATS).await
+    }
+
+    pub async fn close(&mut self) -> Result<(), ClientError> {
+        Ok(self.sink.send(Message::Close(None)).await?)
+    }
+}
+
+// Stream of the packets the server sends, ends when the server closes the connection
+pub struct PacketStream {
+    stream: futures_util::stream::SplitStream<Socket>,
+    wire_format: WireFormat,
+}
+
+impl futures_util::Stream for PacketStream {
+    type Item = Result<ServerPacket, ClientError>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        loop {
+            let message = match futures_util::ready!(self.stream.poll_next_unpin(cx)) {
+                Some(Ok(message)) => message,
+                Some(Err(err)) => return std::task::Poll::Ready(Some(Err(err.into()))),
+                None => return std::task::Poll::Ready(None),
+            };
+            match message {
+                Message::Text(text) => {
+                    return std::task::Poll::Ready(Some(
+                        self.wire_format
+                            .decode_server(&text)
+                            .map_err(ClientError::Packet),
+                    ))
+                }
+                Message::Close(_) => return std::task::Poll::Ready(None),
+                // pings are answered by tungstenite itself
+                _ => continue,
+            }
+        }
+    }
+}
+
+pub struct Client {
+    pub session: Session,
+    sender: PacketSender,
+    stream: PacketStream,
+}
+
+impl Client {
+    pub async fn send(&mut self, packet: &ClientPacket) -> Result<(), ClientError> {
+        self.sender.send(packet).await
+    }
+
+    pub async fn send_move(
+        &mut self,
+        bomb_index: BombIndex,
+        action: BombMoveAction,
+    ) -> Result<(), ClientError> {
+        self.sender.send_move(bomb_index, action).await
+    }
+
+    pub async fn start(&mut self) -> Result<(), ClientError> {
+        self.sender.start().await
+    }
+
+    pub async fn request_stats(&mut self) -> Result<(), ClientError> {
+        self.sender.request_stats().await
+    }
+
+    pub async fn close(&mut self) -> Result<(), ClientError> {
+        self.sender.close().await
+    }
+
+    // None once the server closed the connection
+    pub async fn next_event(&mut self) -> Option<Result<ServerPacket, ClientError>> {
+        self.stream.next().await
+    }
+
+    // for reading and sending packets from different tasks
+    pub fn split(self) -> (Session, PacketSender, PacketStream) {
+        (self.session, self.sender, self.stream)
+    }
+}
+
+// the first packet of the handshake that isn't an ERROR
+async fn handshake_packet(stream: &mut PacketStream) -> Result<ServerPacket, ClientError> {
+    match stream.next().await {
+        Some(Ok(ServerPacket::PacketERROR(code, reason))) => {
+            Err(ClientError::Rejected(code, reason))
+        }
+        Some(packet) => packet,
+        None => Err(ClientError::Closed),
+    }
+}
+
+pub async fn connect(url: &str, options: ClientOptions) -> Result<Client, ClientError> {
+    let mut request = url.into_client_request()?;
+    request.headers_mut().insert(
+        http::header::SEC_WEBSOCKET_PROTOCOL,
+        http::HeaderValue::from_static(options.wire_format.protocol()),
+    );
+
+    let (socket, response) = tokio_tungstenite::connect_async(request).await?;
+    let wire_format = response
+        .headers()
+        .get(http::header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|protocol| protocol.to_str().ok())
+        .and_then(WireFormat::from_protocol)
+        .unwrap_or_default();
+
+    let (sink, stream) = socket.split();
+    let mut sender = PacketSender { sink, wire_format };
+    let mut stream = PacketStream {
+        stream,
+        wire_format,
+    };
+
+    let (bomb_count, server_version, server_capabilities) =
+        match handshake_packet(&mut stream).await? {
+            ServerPacket::PacketHELLO(bomb_count, version, capabilities) => {
+                (bomb_count, version, capabilities)
+            }
+            _ => return Err(ClientError::Handshake("expected a HELLO packet")),
+        };
+
+    // the server rejects versions newer than its own, so the client offers the older of the two
+    // versions and only the capabilities both know
+    let (version, capabilities) = &options.protocol;
+    let version = (*version).min(server_version);
+    let negotiated = Negotiated {
+        version,
+        capabilities: if version < 2 {
+            Vec::new()
+        } else {
+            capabilities
+                .iter()
+                .filter(|c| server_capabilities.contains(c))
+                .cloned()
+                .collect()
+        },
+    };
+
+    let protocol = Some((negotiated.version, negotiated.capabilities.clone()));
+    let join = match options.resume_token {
+        Some(resume_token) => ClientPacket::PacketRESUME(resume_token, protocol),
+        None => ClientPacket::PacketOLLEH(options.preferred_id, protocol, options.player_data),
+    };
+    sender.send(&join).await?;
+
+    let (player_name, player_color, resume_token) = match handshake_packet(&mut stream).await? {
+        ServerPacket::PacketNAME(player_name, player_color, resume_token) => {
+            (player_name, player_color, resume_token)
+        }
+        _ => return Err(ClientError::Handshake("expected a NAME packet")),
+    };
+
+    Ok(Client {
+        session: Session {
+            bomb_count,
+            player_name,
+            player_color,
+            resume_token,
+            negotiated,
+            wire_format,
+        },
+        sender,
+        stream,
+    })
+}
@@ -0,0 +1,37 @@
+// Where the server gets the wall clock from, for event log file names and leaderboard days.
+// Timers, reaction times and event log timestamps always use tokio's time
+// (tokio::time::Instant::now), tests pause it with tokio::time::pause to get the same game on every run.
+pub trait Clock: Send + Sync {
+    fn system_time(&self) -> std::time::SystemTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn system_time(&self) -> std::time::SystemTime {
+        std::time::SystemTime::now()
+    }
+}
+
+// A wall clock that starts at a given time and only moves with tokio's time
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock {
+    start: tokio::time::Instant,
+    start_system_time: std::time::SystemTime,
+}
+
+impl FixedClock {
+    pub fn new(start_system_time: std::time::SystemTime) -> FixedClock {
+        FixedClock {
+            start: tokio::time::Instant::now(),
+            start_system_time,
+        }
+    }
+}
+
+impl Clock for FixedClock {
+    fn system_time(&self) -> std::time::SystemTime {
+        self.start_system_time + self.start.elapsed()
+    }
+}
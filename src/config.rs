@@ -0,0 +1,363 @@
+use crate::bot::BotDifficulty;
+use crate::packet::BombCount;
+use crate::scoring::ScoringKind;
+
+// games never have more bombs than this, whether configured, escalated or set by the admin API
+pub const MAX_BOMB_COUNT: BombCount = 64;
+
+// Settings of a single game (room), every game spawned by the server gets a copy of these
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    // amount of bombs in a game, sent to the client with the HELLO packet
+    pub bomb_count: BombCount,
+    // a bomb is added every bomb_escalation_ms of a round (0 disables it) until there are
+    // bomb_escalation_max bombs, every round starts again with bomb_count bombs
+    pub bomb_escalation_ms: u64,
+    pub bomb_escalation_max: BombCount,
+    // how long a client has to answer HELLO with a OLLEH
+    pub olleh_timeout_ms: u64,
+    // how long a player can hold a bomb before it's taken away
+    pub afk_timeout_ms: u64,
+    // how long the seat and score of a player whose connection dropped are kept for them
+    // to resume, 0 makes a dropped connection the same as leaving
+    pub resume_grace_ms: u64,
+    // a pass made within score_window_ms gets score_base - move_time points
+    pub score_window_ms: u32,
+    pub score_base: u32,
+    // points taken away from a player who held a bomb for too long
+    pub timeout_penalty: u32,
+    // scoring rule of rooms that don't ask for a specific one
+    pub scoring: ScoringKind,
+    // exponential-decay: a pass is worth half as much every decay_half_life_ms
+    pub decay_half_life_ms: u32,
+    // hot-potato: points lost for every second a bomb is held
+    pub hold_cost_per_sec: u32,
+    // extra points for every pass in a row without a timeout (not used by classic)
+    pub streak_bonus: u32,
+    // the game leaves the lobby once this many players (bots included) joined,
+    // or when the host starts it
+    pub lobby_min_players: u32,
+    // a round ends after round_duration_ms or once a player reaches round_target_score
+    // (0 disables the limit), the game returns to the lobby after round_cooldown_ms
+    pub round_duration_ms: u64,
+    pub round_target_score: u32,
+    pub round_cooldown_ms: u32,
+    // bots are added while there are less players than this (0 disables bots)
+    pub bot_fill: u32,
+    pub bot_difficulty: BotDifficulty,
+    // override the reaction time distribution given by bot_difficulty
+    pub bot_reaction_mean_ms: Option<u64>,
+    pub bot_reaction_std_dev_ms: Option<u64>,
+    // seeds the names, colors and bots of a game, games played the same way with the same seed
+    // are the same game, every room gets a random seed without one
+    pub seed: Option<u64>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            bomb_count: 5,
+            bomb_escalation_ms: 0,
+            bomb_escalation_max: 10,
+            olleh_timeout_ms: 10000,
+            afk_timeout_ms: 6000,
+            resume_grace_ms: 30000,
+            score_window_ms: 4000,
+            score_base: 4100,
+            timeout_penalty: 6000,
+            scoring: ScoringKind::Classic,
+            decay_half_life_ms: 1000,
+            hold_cost_per_sec: 1000,
+            streak_bonus: 100,
+            lobby_min_players: 2,
+            round_duration_ms: 0,
+            round_target_score: 0,
+            round_cooldown_ms: 10000,
+            bot_fill: 0,
+            bot_difficulty: BotDifficulty::Normal,
+            bot_reaction_mean_ms: None,
+            bot_reaction_std_dev_ms: None,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: std::net::SocketAddr,
+    // bearer token for the /admin API, the API is disabled without one
+    pub admin_token: Option<String>,
+    // JSON file the leaderboard is kept in, it only lives in memory without one
+    pub leaderboard_path: Option<std::path::PathBuf>,
+    // every game writes its events to a JSON lines file in this directory
+    pub event_log_dir: Option<std::path::PathBuf>,
+    pub game: GameConfig,
+}
+
+// the config is printed on start, the admin token stays out of the logs
+impl std::fmt::Debug for ServerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("bind", &self.bind)
+            .field(
+                "admin_token",
+                &self.admin_token.as_ref().map(|_| "<hidden>"),
+            )
+            .field("leaderboard_path", &self.leaderboard_path)
+            .field("event_log_dir", &self.event_log_dir)
+            .field("game", &self.game)
+            .finish()
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: "0.0.0.0:3000".parse().unwrap(),
+            admin_token: None,
+            leaderboard_path: None,
+            event_log_dir: None,
+            game: GameConfig::default(),
+        }
+    }
+}
+
+// Command line flags, each of them can also be set with an environment variable.
+// Flags override environment variables, which override the config file.
+#[derive(Debug, clap::Parser)]
+#[command(version, about = "Multiplayer bomb passing game server")]
+pub struct ServerArgs {
+    /// TOML file to read the configuration from
+    #[arg(short, long, env = "BOMB_CONFIG")]
+    pub config: Option<std::path::PathBuf>,
+    /// Address to accept connections on
+    #[arg(long, env = "BOMB_BIND")]
+    pub bind: Option<std::net::SocketAddr>,
+    /// Bearer token the admin API asks for, the API is disabled without one
+    #[arg(long, env = "BOMB_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+    /// JSON file to keep the leaderboard in, it's lost on restart without one
+    #[arg(long, env = "BOMB_LEADERBOARD_PATH")]
+    pub leaderboard_path: Option<std::path::PathBuf>,
+    /// Directory to write a replayable event log of every game to
+    #[arg(long, env = "BOMB_EVENT_LOG_DIR")]
+    pub event_log_dir: Option<std::path::PathBuf>,
+    /// Amount of bombs in every game
+    #[arg(long, env = "BOMB_COUNT")]
+    pub bomb_count: Option<BombCount>,
+    /// Milliseconds of a round between adding two bombs, 0 to disable
+    #[arg(long, env = "BOMB_ESCALATION_MS")]
+    pub bomb_escalation_ms: Option<u64>,
+    /// Bombs stop being added once a game has this many
+    #[arg(long, env = "BOMB_ESCALATION_MAX")]
+    pub bomb_escalation_max: Option<BombCount>,
+    /// Milliseconds a client has to answer HELLO with OLLEH
+    #[arg(long, env = "BOMB_OLLEH_TIMEOUT_MS")]
+    pub olleh_timeout_ms: Option<u64>,
+    /// Milliseconds a player can hold a bomb before being considered AFK
+    #[arg(long, env = "BOMB_AFK_TIMEOUT_MS")]
+    pub afk_timeout_ms: Option<u64>,
+    /// Milliseconds a dropped player's seat is kept for them to resume, 0 to disable
+    #[arg(long, env = "BOMB_RESUME_GRACE_MS")]
+    pub resume_grace_ms: Option<u64>,
+    /// Milliseconds after receiving a bomb in which a pass still scores
+    #[arg(long, env = "BOMB_SCORE_WINDOW_MS")]
+    pub score_window_ms: Option<u32>,
+    /// Points for an instant pass, reduced by the milliseconds taken
+    #[arg(long, env = "BOMB_SCORE_BASE")]
+    pub score_base: Option<u32>,
+    /// Points lost when holding a bomb for longer than the AFK timeout
+    #[arg(long, env = "BOMB_TIMEOUT_PENALTY")]
+    pub timeout_penalty: Option<u32>,
+    /// Scoring rule of rooms that don't ask for a specific one
+    #[arg(long, value_enum, env = "BOMB_SCORING")]
+    pub scoring: Option<ScoringKind>,
+    /// Milliseconds for the value of a pass to halve (exponential-decay)
+    #[arg(long, env = "BOMB_DECAY_HALF_LIFE_MS")]
+    pub decay_half_life_ms: Option<u32>,
+    /// Points lost per second a bomb is held (hot-potato)
+    #[arg(long, env = "BOMB_HOLD_COST_PER_SEC")]
+    pub hold_cost_per_sec: Option<u32>,
+    /// Extra points for every pass in a row without a timeout
+    #[arg(long, env = "BOMB_STREAK_BONUS")]
+    pub streak_bonus: Option<u32>,
+    /// Players needed for a game to start without the host starting it
+    #[arg(long, env = "BOMB_LOBBY_MIN_PLAYERS")]
+    pub lobby_min_players: Option<u32>,
+    /// Milliseconds a round lasts, 0 for no time limit
+    #[arg(long, env = "BOMB_ROUND_DURATION_MS")]
+    pub round_duration_ms: Option<u64>,
+    /// Score that ends the round once a player reaches it, 0 for no target
+    #[arg(long, env = "BOMB_ROUND_TARGET_SCORE")]
+    pub round_target_score: Option<u32>,
+    /// Milliseconds between the end of a round and the next lobby
+    #[arg(long, env = "BOMB_ROUND_COOLDOWN_MS")]
+    pub round_cooldown_ms: Option<u32>,
+    /// Add bots to a game while it has less players than this
+    #[arg(long, env = "BOMB_BOT_FILL")]
+    pub bot_fill: Option<u32>,
+    /// How fast bots react
+    #[arg(long, value_enum, env = "BOMB_BOT_DIFFICULTY")]
+    pub bot_difficulty: Option<BotDifficulty>,
+    /// Mean reaction time of bots in milliseconds, overrides the difficulty
+    #[arg(long, env = "BOMB_BOT_REACTION_MEAN_MS")]
+    pub bot_reaction_mean_ms: Option<u64>,
+    /// Standard deviation of the reaction time of bots, overrides the difficulty
+    #[arg(long, env = "BOMB_BOT_REACTION_STD_DEV_MS")]
+    pub bot_reaction_std_dev_ms: Option<u64>,
+    /// Seed of the names, colors and bots of every room, random for each room without one
+    #[arg(long, env = "BOMB_SEED")]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(std::path::PathBuf, std::io::Error),
+    Parse(std::path::PathBuf, toml::de::Error),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, err) => write!(f, "failed reading {}: {}", path.display(), err),
+            Self::Parse(path, err) => write!(f, "failed parsing {}: {}", path.display(), err),
+            Self::Invalid(reason) => write!(f, "invalid configuration: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ServerConfig {
+    pub fn load(args: &ServerArgs) -> Result<ServerConfig, ConfigError> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|err| ConfigError::Read(path.clone(), err))?;
+                toml::from_str(&text).map_err(|err| ConfigError::Parse(path.clone(), err))?
+            }
+            None => ServerConfig::default(),
+        };
+
+        if let Some(bind) = args.bind {
+            config.bind = bind;
+        }
+        if args.admin_token.is_some() {
+            config.admin_token = args.admin_token.clone();
+        }
+        if args.leaderboard_path.is_some() {
+            config.leaderboard_path = args.leaderboard_path.clone();
+        }
+        if args.event_log_dir.is_some() {
+            config.event_log_dir = args.event_log_dir.clone();
+        }
+        let game = &mut config.game;
+        if let Some(bomb_count) = args.bomb_count {
+            game.bomb_count = bomb_count;
+        }
+        if let Some(bomb_escalation_ms) = args.bomb_escalation_ms {
+            game.bomb_escalation_ms = bomb_escalation_ms;
+        }
+        if let Some(bomb_escalation_max) = args.bomb_escalation_max {
+            game.bomb_escalation_max = bomb_escalation_max;
+        }
+        if let Some(olleh_timeout_ms) = args.olleh_timeout_ms {
+            game.olleh_timeout_ms = olleh_timeout_ms;
+        }
+        if let Some(afk_timeout_ms) = args.afk_timeout_ms {
+            game.afk_timeout_ms = afk_timeout_ms;
+        }
+        if let Some(resume_grace_ms) = args.resume_grace_ms {
+            game.resume_grace_ms = resume_grace_ms;
+        }
+        if let Some(score_window_ms) = args.score_window_ms {
+            game.score_window_ms = score_window_ms;
+        }
+        if let Some(score_base) = args.score_base {
+            game.score_base = score_base;
+        }
+        if let Some(timeout_penalty) = args.timeout_penalty {
+            game.timeout_penalty = timeout_penalty;
+        }
+        if let Some(scoring) = args.scoring {
+            game.scoring = scoring;
+        }
+        if let Some(decay_half_life_ms) = args.decay_half_life_ms {
+            game.decay_half_life_ms = decay_half_life_ms;
+        }
+        if let Some(hold_cost_per_sec) = args.hold_cost_per_sec {
+            game.hold_cost_per_sec = hold_cost_per_sec;
+        }
+        if let Some(streak_bonus) = args.streak_bonus {
+            game.streak_bonus = streak_bonus;
+        }
+        if let Some(lobby_min_players) = args.lobby_min_players {
+            game.lobby_min_players = lobby_min_players;
+        }
+        if let Some(round_duration_ms) = args.round_duration_ms {
+            game.round_duration_ms = round_duration_ms;
+        }
+        if let Some(round_target_score) = args.round_target_score {
+            game.round_target_score = round_target_score;
+        }
+        if let Some(round_cooldown_ms) = args.round_cooldown_ms {
+            game.round_cooldown_ms = round_cooldown_ms;
+        }
+        if let Some(bot_fill) = args.bot_fill {
+            game.bot_fill = bot_fill;
+        }
+        if let Some(bot_difficulty) = args.bot_difficulty {
+            game.bot_difficulty = bot_difficulty;
+        }
+        if args.bot_reaction_mean_ms.is_some() {
+            game.bot_reaction_mean_ms = args.bot_reaction_mean_ms;
+        }
+        if args.bot_reaction_std_dev_ms.is_some() {
+            game.bot_reaction_std_dev_ms = args.bot_reaction_std_dev_ms;
+        }
+        if args.seed.is_some() {
+            game.seed = args.seed;
+        }
+
+        if config
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.is_empty())
+        {
+            return Err(ConfigError::Invalid(
+                "admin_token can't be empty".to_string(),
+            ));
+        }
+        config.game.validate()?;
+        Ok(config)
+    }
+}
+
+impl GameConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.bomb_count == 0 {
+            return Err(ConfigError::Invalid(
+                "bomb_count must be at least 1".to_string(),
+            ));
+        }
+        if self.bomb_count > MAX_BOMB_COUNT || self.bomb_escalation_max > MAX_BOMB_COUNT {
+            return Err(ConfigError::Invalid(format!(
+                "bomb_count and bomb_escalation_max can't be more than {MAX_BOMB_COUNT}"
+            )));
+        }
+        if self.afk_timeout_ms == 0 {
+            return Err(ConfigError::Invalid(
+                "afk_timeout_ms must be at least 1".to_string(),
+            ));
+        }
+        if self.decay_half_life_ms == 0 {
+            return Err(ConfigError::Invalid(
+                "decay_half_life_ms must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
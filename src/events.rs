@@ -0,0 +1,271 @@
+use crate::clock::Clock;
+use crate::packet::{BombIndex, BombMoveAction, GameScore, PlayerColor, PlayerID, PlayerName};
+
+// Everything that changes the scoreboard or the bomb positions of a game.
+// A game writes them to its event log, replaying them in order rebuilds the game.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    // the first event of every log, player IDs start from 0 again in every game
+    Start {
+        room: String,
+        unix_ms: u64,
+        // the seed of the game's names, colors and bots
+        #[serde(default)]
+        seed: u64,
+    },
+    Join {
+        player: PlayerID,
+        name: PlayerName,
+        color: PlayerColor,
+        bot: bool,
+    },
+    // the player's score is gone with them, their bombs are moved on with BombPlaced
+    Leave {
+        player: PlayerID,
+    },
+    // every score goes back to 0 and the bombs are spread to these players
+    RoundStart {
+        holders: Vec<PlayerID>,
+    },
+    // nobody holds a bomb until the next round
+    RoundEnd,
+    Pass {
+        bomb: BombIndex,
+        from: PlayerID,
+        to: PlayerID,
+        action: BombMoveAction,
+        ms: u32,
+    },
+    // the player held the bomb for too long and it was passed on for them
+    Timeout {
+        bomb: BombIndex,
+        player: PlayerID,
+        to: PlayerID,
+        ms: u32,
+    },
+    // score is the player's score after the change
+    ScoreChange {
+        player: PlayerID,
+        change: i64,
+        score: GameScore,
+    },
+    // bombs that got a new holder without a pass, i.e. added bombs, the bombs of players who left
+    // and bombs passed on for players whose client doesn't know about them
+    BombPlaced {
+        bomb: BombIndex,
+        holder: PlayerID,
+    },
+    // bombs past the new count are taken away, added ones get a BombPlaced
+    BombCount {
+        count: u32,
+    },
+    // the admin API set every score to 0
+    ScoresReset,
+}
+
+// One line of an event log, at_ms counts from the start of the game
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TimedEvent {
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+// Append-only JSON lines file of the events of a single game. The lines are written by a blocking
+// task so the game never waits on the disk, they are flushed at the end of every round and on close.
+pub struct EventLog {
+    lines: Option<tokio::sync::mpsc::UnboundedSender<(String, bool)>>,
+    writer: Option<tokio::task::JoinHandle<()>>,
+    start: tokio::time::Instant,
+}
+
+impl EventLog {
+    // events go nowhere
+    pub fn disabled() -> EventLog {
+        EventLog {
+            lines: None,
+            writer: None,
+            start: tokio::time::Instant::now(),
+        }
+    }
+
+    // creates <dir>/<room>-<unix ms>.jsonl and writes the Start event to it,
+    // a game starting in the same millisecond as the last one gets <room>-<unix ms>-<n>.jsonl
+    pub fn create(
+        dir: &std::path::Path,
+        room: &str,
+        seed: u64,
+        clock: &dyn Clock,
+    ) -> std::io::Result<EventLog> {
+        let unix_ms = clock
+            .system_time()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut attempt = 0;
+        let file = loop {
+            let path = match attempt {
+                0 => dir.join(format!("{}-{}.jsonl", room, unix_ms)),
+                _ => dir.join(format!("{}-{}-{}.jsonl", room, unix_ms, attempt)),
+            };
+            match std::fs::OpenOptions::new()
+                .append(true)
+                .create_new(true)
+                .open(path)
+            {
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => {
+                    attempt += 1;
+                }
+                file => break file?,
+            }
+        };
+
+        let (lines_tx, lines_rx) = tokio::sync::mpsc::unbounded_channel();
+        let writer = tokio::task::spawn_blocking(move || write_lines(file, lines_rx));
+        let mut log = EventLog {
+            lines: Some(lines_tx),
+            writer: Some(writer),
+            start: tokio::time::Instant::now(),
+        };
+        log.log(GameEvent::Start {
+            room: room.to_string(),
+            unix_ms,
+            seed,
+        });
+        Ok(log)
+    }
+
+    // a log that fails to write stops logging instead of stopping the game
+    pub fn log(&mut self, event: GameEvent) {
+        let Some(lines) = &self.lines else {
+            return;
+        };
+        let flush = event == GameEvent::RoundEnd;
+        let timed = TimedEvent {
+            at_ms: self.start.elapsed().as_millis() as u64,
+            event,
+        };
+        let mut line = serde_json::to_string(&timed).unwrap();
+        line.push('\n');
+        // the writer only stops after failing
+        if lines.send((line, flush)).is_err() {
+            self.lines = None;
+        }
+    }
+
+    // waits until every event logged so far is on the disk
+    pub async fn close(mut self) {
+        self.lines = None;
+        if let Some(writer) = self.writer.take() {
+            writer.await.unwrap();
+        }
+    }
+}
+
+// writes the lines until the log is closed, a line marked with true is flushed right away
+fn write_lines(
+    file: std::fs::File,
+    mut lines: tokio::sync::mpsc::UnboundedReceiver<(String, bool)>,
+) {
+    let mut file = std::io::BufWriter::new(file);
+    while let Some((line, flush)) = lines.blocking_recv() {
+        let mut written = std::io::Write::write_all(&mut file, line.as_bytes());
+        if flush {
+            written = written.and_then(|_| std::io::Write::flush(&mut file));
+        }
+        if let Err(err) = written {
+            println!(
+                "Failed writing the event log, not logging this game anymore: {}",
+                err
+            );
+            return;
+        }
+    }
+    if let Err(err) = std::io::Write::flush(&mut file) {
+        println!("Failed writing the end of the event log: {}", err);
+    }
+}
+
+// The state of a game rebuilt from its events
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Replay {
+    // player id -> name, color and whether they are a bot
+    pub players: std::collections::BTreeMap<PlayerID, (PlayerName, PlayerColor, bool)>,
+    pub scores: std::collections::BTreeMap<PlayerID, GameScore>,
+    // the holder of every bomb, empty outside of rounds
+    pub bombs: Vec<PlayerID>,
+}
+
+impl Replay {
+    pub fn new() -> Replay {
+        Replay::default()
+    }
+
+    pub fn apply(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::Start { .. } => *self = Replay::new(),
+            GameEvent::Join {
+                player,
+                name,
+                color,
+                bot,
+            } => {
+                self.players
+                    .insert(*player, (name.clone(), color.clone(), *bot));
+                self.scores.insert(*player, 0);
+            }
+            GameEvent::Leave { player } => {
+                self.players.remove(player);
+                self.scores.remove(player);
+            }
+            GameEvent::RoundStart { holders } => {
+                for score in self.scores.values_mut() {
+                    *score = 0;
+                }
+                self.bombs = holders.clone();
+            }
+            GameEvent::RoundEnd => self.bombs.clear(),
+            GameEvent::Pass { bomb, to, .. } | GameEvent::Timeout { bomb, to, .. } => {
+                self.place(*bomb, *to)
+            }
+            GameEvent::ScoreChange { player, score, .. } => {
+                self.scores.insert(*player, *score);
+            }
+            GameEvent::BombPlaced { bomb, holder } => self.place(*bomb, *holder),
+            GameEvent::BombCount { count } => self.bombs.truncate(*count as usize),
+            GameEvent::ScoresReset => {
+                for score in self.scores.values_mut() {
+                    *score = 0;
+                }
+            }
+        }
+    }
+
+    // added bombs are placed right after the bomb count grew, so they come in order
+    fn place(&mut self, bomb: BombIndex, holder: PlayerID) {
+        match self.bombs.get_mut(bomb as usize) {
+            Some(current) => *current = holder,
+            None => self.bombs.push(holder),
+        }
+    }
+
+    pub fn name(&self, player: PlayerID) -> &str {
+        self.players
+            .get(&player)
+            .map_or("?", |(name, _, _)| name.as_str())
+    }
+
+    // players with their scores, highest score first
+    pub fn scoreboard(&self) -> Vec<(PlayerID, GameScore)> {
+        let mut scoreboard = self
+            .scores
+            .iter()
+            .map(|(player, score)| (*player, *score))
+            .collect::<Vec<_>>();
+        scoreboard.sort_by(|(a_player, a_score), (b_player, b_score)| {
+            b_score.cmp(a_score).then(a_player.cmp(b_player))
+        });
+        scoreboard
+    }
+}
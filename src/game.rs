@@ -0,0 +1,1569 @@
+use crate::config::GameConfig;
+use crate::events::GameEvent;
+use crate::packet::{
+    valid_player_color, valid_player_name, BombCount, BombIndex, BombMoveAction, BombPosition,
+    GamePhase, GameScoareboard, GameScore, PlayerColor, PlayerCount, PlayerData, PlayerID,
+    PlayerName, PlayerStats, PreferredID, ResumeToken,
+};
+use crate::ring::SeatRing;
+use crate::scoring::{apply_score_change, ScoringPolicy};
+use crate::stats::StatsTracker;
+use rand::{Rng, SeedableRng};
+
+// tells a bomb handed to a player apart from the earlier and later times it was handed out,
+// answers for a handoff the game doesn't wait for anymore are ignored
+pub type HandoffID = u64;
+
+// What the game tells a single player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayerUpdate {
+    BombMoved(BombIndex, BombPosition),
+    // the players waiting in the lobby changed
+    Lobby(PlayerCount, Vec<PlayerData>),
+    // the game left the lobby, bomb updates follow
+    Begin,
+    // the round is over, the next one starts after the cooldown (in milliseconds)
+    End(u32, GameScoareboard),
+    // the game wants the player to leave, the player should tell the game after leaving
+    Leave,
+    // the server is shutting down, the game ends with this final scoreboard
+    Shutdown(GameScoareboard),
+    // bombs were added or taken away, sent before any update about the new bombs
+    BombCount(BombCount),
+    // the stats of every player, after the player asked for them or at the end of a round
+    Stats(Vec<(PlayerName, PlayerStats)>),
+}
+
+// What the game wants its driver to do, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Send(PlayerID, PlayerUpdate),
+    // the player holds the bomb now, their answer goes to pass, timeout or drop_handoff
+    GiveBomb(PlayerID, BombIndex, HandoffID),
+    // a bot joins like any other player once it's started, it makes its choices with a
+    // random generator seeded with this
+    SpawnBot(u64),
+    // the scores players that chose their name reached in a round, or before leaving in the middle
+    // of one, bots and players with a random name aren't on the leaderboard
+    Results(Vec<(PlayerName, GameScore)>),
+    Log(GameEvent),
+    // a line for the server's console
+    Print(String),
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RoomInfo {
+    pub phase: GamePhase,
+    pub paused: bool,
+    pub bomb_count: BombCount,
+    // in seat order
+    pub players: Vec<PlayerInfo>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct PlayerInfo {
+    pub id: PlayerID,
+    pub name: PlayerName,
+    pub color: PlayerColor,
+    pub score: GameScore,
+    pub bot: bool,
+    // suspended players keep their seat until they resume or their grace period ends
+    pub connected: bool,
+    // the bombs at this player, including ones not handed out yet
+    pub bombs: Vec<BombIndex>,
+    pub stats: PlayerStats,
+}
+
+/// The rules of a game without any I/O: every method takes the current time, changes the state
+/// and returns what the players have to be told, even the console output is an effect.
+/// The same calls with the same seed always return the same effects. Players that are present
+/// are expected to receive their updates, the driver keeps a channel for each of them.
+pub struct GameState {
+    config: GameConfig,
+    scoring: Box<dyn ScoringPolicy>,
+    // names, colors and the seeds of bots come from rng
+    seed: u64,
+    rng: rand::rngs::StdRng,
+
+    // the bomb count every round starts with, can be changed with the admin API
+    room_bomb_count: BombCount,
+    // bots that are spawned but haven't joined yet
+    pending_bots: usize,
+
+    // the players in seat order (for calculating new bomb position),
+    // players whose connection dropped are away but keep their seat
+    players: SeatRing,
+    // IDs only tell players apart, the seat a player gets comes from their preferred ID
+    next_player_id: PlayerID,
+    // player id -> player name + color
+    players_data: std::collections::BTreeMap<PlayerID, PlayerData>,
+    // names players in other rooms play under, nobody here gets them
+    reserved_names: std::collections::BTreeSet<PlayerName>,
+    // players in the order they joined, the first one that isn't a bot hosts the lobby
+    join_order: Vec<PlayerID>,
+    // resume token -> the player it belongs to
+    resume_tokens: std::collections::BTreeMap<ResumeToken, PlayerID>,
+    // player id -> how many bombs the player's client knows about, the bombs past that are
+    // passed on for them, players missing here know about every bomb
+    bomb_limits: std::collections::BTreeMap<PlayerID, BombCount>,
+    // players whose connection dropped -> when their seat is given up
+    // they are out of the ring but keep their data and score until then
+    suspended: std::collections::BTreeMap<PlayerID, tokio::time::Instant>,
+
+    // player id -> player score
+    players_score: std::collections::BTreeMap<PlayerID, GameScore>,
+    // player id -> passes made since the player's last timeout
+    players_streak: std::collections::BTreeMap<PlayerID, u32>,
+    // player id -> what the player did since joining, kept across rounds
+    players_stats: std::collections::BTreeMap<PlayerID, StatsTracker>,
+
+    // bots that are playing, and bots that are asked to leave but haven't yet
+    bots: std::collections::BTreeSet<PlayerID>,
+    leaving_bots: std::collections::BTreeSet<PlayerID>,
+    // players playing under a random name, their scores don't go on the leaderboard
+    anonymous: std::collections::BTreeSet<PlayerID>,
+
+    // the game stays in the lobby until there are enough players or the host starts it,
+    // bombs are only handed out after that
+    phase: GamePhase,
+    start_requested: bool,
+    lobby_changed: bool,
+    // a round ends at round_end (if there's a time limit) or when someone reaches the target score
+    round_over: bool,
+    round_end: tokio::time::Instant,
+    cooldown_end: tokio::time::Instant,
+    // set by the admin API, the time spent paused is added to round_end on resuming
+    paused: bool,
+    paused_at: tokio::time::Instant,
+
+    // bombs are only added or taken away when the game settles, by setting wanted_bomb_count
+    bomb_count: BombCount,
+    wanted_bomb_count: BombCount,
+    next_escalation: tokio::time::Instant,
+
+    bomb_pos: Vec<PlayerID>,
+    // bombs that are at bomb_pos but weren't handed to the holder yet,
+    // they are handed out when the game settles unless it's paused
+    parked_bombs: std::collections::BTreeSet<BombIndex>,
+    // bombs handed to their holder, waiting for an answer -> the bomb, the holder and when
+    handoffs: std::collections::BTreeMap<HandoffID, (BombIndex, PlayerID, tokio::time::Instant)>,
+    next_handoff: HandoffID,
+}
+
+impl GameState {
+    pub fn new(
+        config: GameConfig,
+        scoring: Box<dyn ScoringPolicy>,
+        seed: u64,
+        now: tokio::time::Instant,
+    ) -> GameState {
+        GameState {
+            seed,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+            room_bomb_count: config.bomb_count,
+            pending_bots: 0,
+            players: SeatRing::new(),
+            next_player_id: 0,
+            players_data: std::collections::BTreeMap::new(),
+            reserved_names: std::collections::BTreeSet::new(),
+            join_order: Vec::new(),
+            resume_tokens: std::collections::BTreeMap::new(),
+            bomb_limits: std::collections::BTreeMap::new(),
+            suspended: std::collections::BTreeMap::new(),
+            players_score: std::collections::BTreeMap::new(),
+            players_streak: std::collections::BTreeMap::new(),
+            players_stats: std::collections::BTreeMap::new(),
+            bots: std::collections::BTreeSet::new(),
+            leaving_bots: std::collections::BTreeSet::new(),
+            anonymous: std::collections::BTreeSet::new(),
+            phase: GamePhase::Lobby,
+            start_requested: false,
+            lobby_changed: false,
+            round_over: false,
+            round_end: now,
+            cooldown_end: now,
+            paused: false,
+            paused_at: now,
+            bomb_count: config.bomb_count,
+            wanted_bomb_count: config.bomb_count,
+            next_escalation: now,
+            bomb_pos: Vec::new(),
+            parked_bombs: std::collections::BTreeSet::new(),
+            handoffs: std::collections::BTreeMap::new(),
+            next_handoff: 0,
+            config,
+            scoring,
+        }
+    }
+
+    // a new game in the same room once everyone left, player IDs start from 0 again
+    // but the room's bomb count and the bots still starting up are kept,
+    // the seed of the new game comes from the last one
+    pub fn restart(mut self, now: tokio::time::Instant) -> GameState {
+        let room_bomb_count = self.room_bomb_count;
+        let pending_bots = self.pending_bots;
+        let seed = self.rng.gen();
+        let mut game = GameState::new(self.config, self.scoring, seed, now);
+        game.room_bomb_count = room_bomb_count;
+        game.bomb_count = room_bomb_count;
+        game.wanted_bomb_count = room_bomb_count;
+        game.pending_bots = pending_bots;
+        game
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn bomb_count(&self) -> BombCount {
+        self.bomb_count
+    }
+
+    // nobody is playing or can resume anymore
+    pub fn is_abandoned(&self) -> bool {
+        self.players.is_empty() && self.suspended.is_empty()
+    }
+
+    // the next time tick has something to do
+    pub fn next_deadline(&self) -> Option<tokio::time::Instant> {
+        let playing = self.phase == GamePhase::Playing && !self.paused;
+        [
+            (playing && self.config.round_duration_ms > 0).then_some(self.round_end),
+            (playing && self.config.bomb_escalation_ms > 0).then_some(self.next_escalation),
+            self.suspended.values().min().copied(),
+            (self.phase == GamePhase::Cooldown).then_some(self.cooldown_end),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    // the player gets the name and color they asked for if they can, the data they got is returned
+    pub fn join(
+        &mut self,
+        preferred_id: PreferredID,
+        requested_data: Option<PlayerData>,
+        bot: bool,
+        bomb_limit: Option<BombCount>,
+        resume_token: ResumeToken,
+        now: tokio::time::Instant,
+    ) -> (PlayerID, PlayerData, Vec<Effect>) {
+        let mut effects = Vec::new();
+        let player_id = self.next_player_id;
+        self.next_player_id += 1;
+        effects.push(Effect::Print("A new player joined...".to_string()));
+        effects.push(Effect::Print(format!(
+            "ID of the new player: {player_id}, preferred position: {preferred_id}"
+        )));
+
+        if bot {
+            self.pending_bots -= 1;
+            self.bots.insert(player_id);
+        }
+        let (player_data, named) = self.pick_player_data(requested_data, bot, &mut effects);
+        if !named {
+            self.anonymous.insert(player_id);
+        }
+        self.players.join(player_id, preferred_id);
+        effects.push(Effect::Log(GameEvent::Join {
+            player: player_id,
+            name: player_data.0.clone(),
+            color: player_data.1.clone(),
+            bot,
+        }));
+        self.players_data.insert(player_id, player_data.clone());
+        self.resume_tokens.insert(resume_token, player_id);
+        if let Some(bomb_limit) = bomb_limit {
+            self.bomb_limits.insert(player_id, bomb_limit);
+        }
+        self.players_score.insert(player_id, 0);
+        self.join_order.push(player_id);
+        self.lobby_changed = true;
+
+        self.catch_up(player_id, &mut effects);
+        self.settle(now, &mut effects);
+        (player_id, player_data, effects)
+    }
+
+    // None if the token doesn't belong to a suspended player
+    pub fn resume(
+        &mut self,
+        resume_token: &ResumeToken,
+        bomb_limit: Option<BombCount>,
+        now: tokio::time::Instant,
+    ) -> Option<(PlayerID, PlayerData, Vec<Effect>)> {
+        let player_id = self
+            .resume_tokens
+            .get(resume_token)
+            .copied()
+            .filter(|player_id| self.suspended.contains_key(player_id))?;
+        let mut effects = vec![Effect::Print(format!("{player_id} resumed..."))];
+        self.suspended.remove(&player_id);
+        self.players.set_present(player_id, true);
+        // the new connection may know about a different amount of bombs
+        match bomb_limit {
+            Some(bomb_limit) => self.bomb_limits.insert(player_id, bomb_limit),
+            None => self.bomb_limits.remove(&player_id),
+        };
+        self.lobby_changed = true;
+
+        self.catch_up(player_id, &mut effects);
+        self.settle(now, &mut effects);
+        Some((player_id, self.players_data[&player_id].clone(), effects))
+    }
+
+    pub fn leave(&mut self, player_id: PlayerID, now: tokio::time::Instant) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        effects.push(Effect::Log(GameEvent::Leave { player: player_id }));
+        if self.phase == GamePhase::Playing
+            && !self.bots.contains(&player_id)
+            && !self.leaving_bots.contains(&player_id)
+        {
+            effects.push(Effect::Results(self.round_results([player_id])));
+        }
+        // the seat is given up once the bombs of the player moved on
+        self.players.set_present(player_id, false);
+        self.bots.remove(&player_id);
+        self.leaving_bots.remove(&player_id);
+        self.lobby_changed = true;
+        self.forget(player_id);
+
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // the seat is kept for the player to resume if the game allows it, otherwise they leave
+    pub fn disconnect(&mut self, player_id: PlayerID, now: tokio::time::Instant) -> Vec<Effect> {
+        if self.config.resume_grace_ms == 0 {
+            return self.leave(player_id, now);
+        }
+        let mut effects = vec![Effect::Print(format!(
+            "{player_id} disconnected, keeping their seat for a while..."
+        ))];
+        self.players.set_present(player_id, false);
+        self.suspended.insert(
+            player_id,
+            now + tokio::time::Duration::from_millis(self.config.resume_grace_ms),
+        );
+        self.lobby_changed = true;
+
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // only the host can start the game
+    pub fn start(&mut self, player_id: PlayerID, now: tokio::time::Instant) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if self.phase != GamePhase::Lobby {
+            effects.push(Effect::Print(format!(
+                "{player_id} asked to start a game that isn't in the lobby..."
+            )));
+        } else if self.host() != Some(player_id) {
+            effects.push(Effect::Print(format!(
+                "{player_id} asked to start the game without being the host..."
+            )));
+        } else {
+            self.start_requested = true;
+        }
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    pub fn stats(&self, player_id: PlayerID) -> Vec<Effect> {
+        if !self.players.contains(&player_id) {
+            return Vec::new();
+        }
+        vec![Effect::Send(
+            player_id,
+            PlayerUpdate::Stats(self.render_stats()),
+        )]
+    }
+
+    // the player passed the bomb of the handoff on
+    pub fn pass(
+        &mut self,
+        handoff: HandoffID,
+        action: BombMoveAction,
+        now: tokio::time::Instant,
+    ) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if let Some((bomb_index, holder, given_at)) = self.take_handoff(handoff, &mut effects) {
+            let held = now.saturating_duration_since(given_at);
+            let streak = self.players_streak.get(&holder).unwrap_or(&0) + 1;
+            self.players_streak.insert(holder, streak);
+            let move_score = self.scoring.on_pass(held) + self.scoring.streak_bonus(streak);
+            self.change_score(holder, move_score, &mut effects);
+            effects.push(Effect::Print(format!("{holder} got {move_score} points!")));
+            self.players_stats.entry(holder).or_default().on_pass(held);
+            if self.config.round_target_score > 0
+                && self.players_score[&holder] >= self.config.round_target_score
+            {
+                effects.push(Effect::Print(format!(
+                    "{holder} reached the target score..."
+                )));
+                self.round_over = true;
+            }
+
+            let to = self.hand_on(bomb_index, holder, action);
+            effects.push(Effect::Log(GameEvent::Pass {
+                bomb: bomb_index,
+                from: holder,
+                to,
+                action,
+                ms: held.as_millis() as u32,
+            }));
+            self.announce_bomb(bomb_index, to, &mut effects);
+        }
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // the player held the bomb of the handoff for too long, it's passed on for them
+    pub fn timeout(&mut self, handoff: HandoffID, now: tokio::time::Instant) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        match self.take_handoff(handoff, &mut effects) {
+            // nobody has to play while the game is paused, the holder is handed
+            // the bomb again with a new AFK timeout once it resumes
+            Some((bomb_index, holder, given_at)) if self.paused || self.paused_at > given_at => {
+                effects.push(Effect::Print(format!(
+                    "{holder} held the bomb through a pause..."
+                )));
+                self.parked_bombs.insert(bomb_index);
+            }
+            Some((bomb_index, holder, given_at)) => {
+                let held = now.saturating_duration_since(given_at);
+                self.players_streak.insert(holder, 0);
+                let penalty = self.scoring.on_timeout(held);
+                self.change_score(holder, penalty, &mut effects);
+                effects.push(Effect::Print(format!(
+                    "{holder} loses {} points!",
+                    -penalty
+                )));
+                self.players_stats
+                    .entry(holder)
+                    .or_default()
+                    .on_timeout(held);
+
+                let to = self.hand_on(bomb_index, holder, BombMoveAction::R1);
+                effects.push(Effect::Log(GameEvent::Timeout {
+                    bomb: bomb_index,
+                    player: holder,
+                    to,
+                    ms: held.as_millis() as u32,
+                }));
+                self.announce_bomb(bomb_index, to, &mut effects);
+            }
+            None => {}
+        }
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // the holder went away without answering
+    pub fn drop_handoff(&mut self, handoff: HandoffID, now: tokio::time::Instant) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if self.take_handoff(handoff, &mut effects).is_some() {
+            // the bomb moves on once the game hears that the player left
+            effects.push(Effect::Print(
+                "Player leaved before moving bomb".to_string(),
+            ));
+        }
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // handles the round timer, bomb escalation, the end of the cooldown and suspended players
+    // who didn't come back in time
+    pub fn tick(&mut self, now: tokio::time::Instant) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        let playing = self.phase == GamePhase::Playing && !self.paused;
+        if playing && self.config.round_duration_ms > 0 && self.round_end <= now {
+            effects.push(Effect::Print("Round time is up...".to_string()));
+            self.round_over = true;
+        }
+        if playing && self.config.bomb_escalation_ms > 0 && self.next_escalation <= now {
+            self.next_escalation +=
+                tokio::time::Duration::from_millis(self.config.bomb_escalation_ms);
+            if self.wanted_bomb_count < self.config.bomb_escalation_max {
+                self.wanted_bomb_count += 1;
+            }
+        }
+
+        let expired = self
+            .suspended
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(player_id, _)| *player_id)
+            .collect::<Vec<_>>();
+        for expired_player in expired {
+            effects.push(Effect::Print(format!(
+                "{expired_player} didn't come back in time..."
+            )));
+            effects.push(Effect::Log(GameEvent::Leave {
+                player: expired_player,
+            }));
+            if self.phase == GamePhase::Playing {
+                effects.push(Effect::Results(self.round_results([expired_player])));
+            }
+            self.suspended.remove(&expired_player);
+            self.forget(expired_player);
+        }
+
+        if self.phase == GamePhase::Cooldown && self.cooldown_end <= now {
+            effects.push(Effect::Print(
+                "Cooldown is over, back to the lobby...".to_string(),
+            ));
+            self.phase = GamePhase::Lobby;
+            self.lobby_changed = true;
+        }
+
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // answers whether the player was in the game
+    pub fn kick(&mut self, player_id: PlayerID, now: tokio::time::Instant) -> (bool, Vec<Effect>) {
+        if self.players.contains(&player_id) {
+            // the player leaves like they asked to, the seat is freed once they did
+            (
+                true,
+                vec![
+                    Effect::Print(format!("Kicking {player_id}...")),
+                    Effect::Send(player_id, PlayerUpdate::Leave),
+                ],
+            )
+        } else if let Some(deadline) = self.suspended.get_mut(&player_id) {
+            // gone at the next tick
+            *deadline = now;
+            (
+                true,
+                vec![Effect::Print(format!(
+                    "Kicking suspended player {player_id}..."
+                ))],
+            )
+        } else {
+            (false, Vec::new())
+        }
+    }
+
+    pub fn reset_scores(&mut self) -> Vec<Effect> {
+        for score in self.players_score.values_mut() {
+            *score = 0;
+        }
+        self.players_streak.clear();
+        vec![
+            Effect::Print("Resetting scores...".to_string()),
+            Effect::Log(GameEvent::ScoresReset),
+        ]
+    }
+
+    // bombs aren't handed out and the round timer stops while the game is paused,
+    // holders whose AFK timeout runs out in the meantime keep their bomb without a penalty
+    pub fn pause(&mut self, now: tokio::time::Instant) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if !self.paused {
+            effects.push(Effect::Print("Game paused...".to_string()));
+            self.paused = true;
+            self.paused_at = now;
+        }
+        effects
+    }
+
+    pub fn unpause(&mut self, now: tokio::time::Instant) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if self.paused {
+            effects.push(Effect::Print("Game resumed...".to_string()));
+            self.paused = false;
+            let paused_for = now.saturating_duration_since(self.paused_at);
+            self.round_end += paused_for;
+            self.next_escalation += paused_for;
+        }
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // also the bomb count the following rounds start with
+    pub fn set_bomb_count(
+        &mut self,
+        bomb_count: BombCount,
+        now: tokio::time::Instant,
+    ) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        self.room_bomb_count = bomb_count;
+        self.wanted_bomb_count = bomb_count;
+        self.settle(now, &mut effects);
+        effects
+    }
+
+    // the server is shutting down, the game ends with the current scores
+    pub fn shutdown(&self) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if self.phase == GamePhase::Playing {
+            effects.push(Effect::Results(
+                self.round_results(self.players_score.keys().copied()),
+            ));
+        }
+        let board = self.scoreboard();
+        for player_id in self.players.iter() {
+            effects.push(Effect::Send(
+                player_id,
+                PlayerUpdate::Shutdown(board.clone()),
+            ));
+        }
+        effects
+    }
+
+    // the names players joining later can't get, until reserve_names is called again
+    pub fn reserve_names(&mut self, names: std::collections::BTreeSet<PlayerName>) {
+        self.reserved_names = names;
+    }
+
+    // the names of everyone who is playing or can still resume
+    pub fn player_names(&self) -> std::collections::BTreeSet<PlayerName> {
+        self.players_data
+            .values()
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    // players with higher scores come first, ties are broken by the higher player id
+    pub fn scoreboard(&self) -> GameScoareboard {
+        let mut scoreboard_map = std::collections::BTreeMap::new();
+
+        for (player_id, score) in &self.players_score {
+            scoreboard_map.insert((score, player_id), (&self.players_data[player_id], score));
+        }
+
+        let mut scoreboard_string = String::new();
+        for (_, (data, score)) in scoreboard_map.into_iter().rev() {
+            scoreboard_string.push_str(&format!("{}\n{}", data.0, data.1));
+            scoreboard_string.push_str(&format!("\n{score}\n"));
+        }
+        scoreboard_string
+    }
+
+    // what spectators see: the phase, the holder of every bomb and every player in the ring
+    // in seat order, in the same layout as the scoreboard
+    pub fn spectator_view(&self) -> (GamePhase, Vec<PlayerName>, GameScoareboard) {
+        // the ring is only empty while every player is suspended, nobody holds a bomb then
+        let holders = if self.phase == GamePhase::Playing && !self.players.is_empty() {
+            self.bomb_pos
+                .iter()
+                .map(|holder| self.players_data[holder].0.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut ring_string = String::new();
+        for player_id in self.players.iter() {
+            let (name, color) = &self.players_data[&player_id];
+            ring_string.push_str(&format!(
+                "{}\n{}\n{}\n",
+                name,
+                color,
+                self.players_score.get(&player_id).unwrap_or(&0)
+            ));
+        }
+        (self.phase, holders, ring_string)
+    }
+
+    pub fn room_info(&self) -> RoomInfo {
+        // seats of players who are already gone are left out
+        let players = self
+            .players
+            .seated()
+            .filter_map(|player_id| Some((player_id, self.players_data.get(&player_id)?)))
+            .map(|(player_id, (name, color))| PlayerInfo {
+                id: player_id,
+                name: name.clone(),
+                color: color.clone(),
+                score: *self.players_score.get(&player_id).unwrap_or(&0),
+                bot: self.bots.contains(&player_id) || self.leaving_bots.contains(&player_id),
+                connected: self.players.contains(&player_id),
+                bombs: (0..self.bomb_pos.len() as BombIndex)
+                    .filter(|bomb_index| self.bomb_pos[*bomb_index as usize] == player_id)
+                    .collect(),
+                stats: self
+                    .players_stats
+                    .get(&player_id)
+                    .map(StatsTracker::summary)
+                    .unwrap_or_default(),
+            })
+            .collect();
+        RoomInfo {
+            phase: self.phase,
+            paused: self.paused,
+            bomb_count: self.bomb_count,
+            players,
+        }
+    }
+
+    fn random_player_data(&mut self, bot: bool) -> PlayerData {
+        (
+            format!(
+                "{}{:04X}",
+                if bot { "Bot" } else { "Player" },
+                self.rng.gen::<u16>()
+            ),
+            format!("#{:06X}", self.rng.gen::<u32>() >> 8),
+        )
+    }
+
+    // uses the name and color a player asked for if they are valid, names are unique within a room
+    // also returns whether the player got the name they asked for
+    fn pick_player_data(
+        &mut self,
+        requested: Option<PlayerData>,
+        bot: bool,
+        effects: &mut Vec<Effect>,
+    ) -> (PlayerData, bool) {
+        let mut named = false;
+        let (mut name, mut color) = self.random_player_data(bot);
+        if let Some((requested_name, requested_color)) = requested {
+            if !valid_player_name(&requested_name) {
+                effects.push(Effect::Print(format!(
+                    "Player asked for an invalid name {:?}...",
+                    requested_name
+                )));
+            } else if self.name_taken(&requested_name) {
+                effects.push(Effect::Print(format!(
+                    "Player asked for the name {requested_name} which is taken..."
+                )));
+            } else {
+                name = requested_name;
+                named = true;
+            }
+            if valid_player_color(&requested_color) {
+                color = requested_color;
+            } else {
+                effects.push(Effect::Print(format!(
+                    "Player asked for an invalid color {:?}...",
+                    requested_color
+                )));
+            }
+        }
+        while self.name_taken(&name) {
+            name = self.random_player_data(bot).0;
+        }
+        ((name, color), named)
+    }
+
+    fn name_taken(&self, name: &str) -> bool {
+        self.reserved_names.contains(name)
+            || self.players_data.values().any(|(taken, _)| taken == name)
+    }
+
+    fn host(&self) -> Option<PlayerID> {
+        self.join_order
+            .iter()
+            .find(|player_id| {
+                self.players.contains(player_id)
+                    && !self.bots.contains(player_id)
+                    && !self.leaving_bots.contains(player_id)
+            })
+            .copied()
+    }
+
+    // a player who is gone for good, their seat stays until their bombs moved on
+    fn forget(&mut self, player_id: PlayerID) {
+        self.join_order.retain(|joined| *joined != player_id);
+        self.players_data.remove(&player_id);
+        self.players_score.remove(&player_id);
+        self.players_stats.remove(&player_id);
+        self.players_streak.remove(&player_id);
+        self.resume_tokens
+            .retain(|_, token_player| *token_player != player_id);
+        self.bomb_limits.remove(&player_id);
+        self.anonymous.remove(&player_id);
+    }
+
+    // a player joining or resuming in the middle of a round is told where every bomb is
+    fn catch_up(&self, player_id: PlayerID, effects: &mut Vec<Effect>) {
+        if self.phase != GamePhase::Playing {
+            return;
+        }
+        effects.push(Effect::Send(player_id, PlayerUpdate::Begin));
+        for bomb_index in 0..self.bomb_count {
+            effects.push(Effect::Send(
+                player_id,
+                PlayerUpdate::BombMoved(
+                    bomb_index,
+                    self.players
+                        .relative_position(self.bomb_pos[bomb_index as usize], player_id),
+                ),
+            ));
+        }
+    }
+
+    fn take_handoff(
+        &mut self,
+        handoff: HandoffID,
+        effects: &mut Vec<Effect>,
+    ) -> Option<(BombIndex, PlayerID, tokio::time::Instant)> {
+        let Some((bomb_index, holder, given_at)) = self.handoffs.remove(&handoff) else {
+            effects.push(Effect::Print(
+                "The bomb was moved or removed before the game got the move".to_string(),
+            ));
+            return None;
+        };
+        if !self.players.contains(&holder) {
+            effects.push(Effect::Print(
+                "Player leaved before the game got their move".to_string(),
+            ));
+            // with nobody left in the ring the bomb waits for its holder to resume,
+            // otherwise it was already handed on without them
+            self.parked_bombs.insert(bomb_index);
+            return None;
+        }
+        Some((bomb_index, holder, given_at))
+    }
+
+    fn change_score(&mut self, player_id: PlayerID, change: i64, effects: &mut Vec<Effect>) {
+        let score = apply_score_change(*self.players_score.get(&player_id).unwrap_or(&0), change);
+        self.players_score.insert(player_id, score);
+        effects.push(Effect::Log(GameEvent::ScoreChange {
+            player: player_id,
+            change,
+            score,
+        }));
+    }
+
+    // moves the bomb from its holder, it's handed to the new holder when the game settles
+    fn hand_on(
+        &mut self,
+        bomb_index: BombIndex,
+        holder: PlayerID,
+        action: BombMoveAction,
+    ) -> PlayerID {
+        let to = self.players.move_bomb(holder, action);
+        self.bomb_pos[bomb_index as usize] = to;
+        self.parked_bombs.insert(bomb_index);
+        to
+    }
+
+    // the bomb goes on to the right past players whose client doesn't know about it,
+    // None if nobody in the ring does
+    fn pass_blind(
+        &mut self,
+        bomb_index: BombIndex,
+        holder: PlayerID,
+        effects: &mut Vec<Effect>,
+    ) -> Option<PlayerID> {
+        let mut to = holder;
+        for _ in 0..self.players.len() {
+            if self
+                .bomb_limits
+                .get(&to)
+                .is_none_or(|bomb_limit| bomb_index < *bomb_limit)
+            {
+                if to != holder {
+                    self.bomb_pos[bomb_index as usize] = to;
+                    effects.push(Effect::Log(GameEvent::BombPlaced {
+                        bomb: bomb_index,
+                        holder: to,
+                    }));
+                    self.announce_bomb(bomb_index, to, effects);
+                }
+                return Some(to);
+            }
+            to = self.players.move_bomb(to, BombMoveAction::R1);
+        }
+        None
+    }
+
+    // tells every player but the holder where the bomb is now
+    fn announce_bomb(&self, bomb_index: BombIndex, holder: PlayerID, effects: &mut Vec<Effect>) {
+        for player_id in self.players.iter() {
+            if player_id != holder {
+                effects.push(Effect::Send(
+                    player_id,
+                    PlayerUpdate::BombMoved(
+                        bomb_index,
+                        self.players.relative_position(holder, player_id),
+                    ),
+                ));
+            }
+        }
+    }
+
+    fn round_results(
+        &self,
+        player_ids: impl IntoIterator<Item = PlayerID>,
+    ) -> Vec<(PlayerName, GameScore)> {
+        player_ids
+            .into_iter()
+            .filter(|player_id| {
+                !self.bots.contains(player_id)
+                    && !self.leaving_bots.contains(player_id)
+                    && !self.anonymous.contains(player_id)
+            })
+            .map(|player_id| {
+                (
+                    self.players_data[&player_id].0.clone(),
+                    *self.players_score.get(&player_id).unwrap_or(&0),
+                )
+            })
+            .collect()
+    }
+
+    // the stats of every player who still has a seat, in seat order
+    fn render_stats(&self) -> Vec<(PlayerName, PlayerStats)> {
+        self.players
+            .seated()
+            .filter_map(|player_id| {
+                let (name, _) = self.players_data.get(&player_id)?;
+                let stats = self
+                    .players_stats
+                    .get(&player_id)
+                    .map(StatsTracker::summary);
+                Some((name.clone(), stats.unwrap_or_default()))
+            })
+            .collect()
+    }
+
+    // applies everything that follows from the last change: bots come and go, bombs of
+    // players who left move on, rounds end and start, the bomb count changes and parked
+    // bombs are handed out
+    fn settle(&mut self, now: tokio::time::Instant, effects: &mut Vec<Effect>) {
+        // the driver starts a new game, nothing here matters anymore
+        if self.is_abandoned() {
+            return;
+        }
+
+        // fill the ring up to bot_fill players with bots, and let bots go when players take their seats
+        let humans = self.players.len() - self.bots.len() - self.leaving_bots.len();
+        let seats = humans + self.bots.len() + self.pending_bots;
+        let bot_fill = self.config.bot_fill as usize;
+        let leaving_count = if humans == 0 {
+            self.bots.len()
+        } else {
+            seats.saturating_sub(bot_fill).min(self.bots.len())
+        };
+        for bot_id in self
+            .bots
+            .iter()
+            .take(leaving_count)
+            .copied()
+            .collect::<Vec<_>>()
+        {
+            effects.push(Effect::Print(format!("Asking bot {bot_id} to leave...")));
+            self.bots.remove(&bot_id);
+            self.leaving_bots.insert(bot_id);
+            effects.push(Effect::Send(bot_id, PlayerUpdate::Leave));
+        }
+        if humans > 0 && seats < bot_fill {
+            for _ in seats..bot_fill {
+                effects.push(Effect::SpawnBot(self.rng.gen()));
+            }
+            self.pending_bots += bot_fill - seats;
+        }
+
+        // bombs held by players who left or dropped go on to the next player in the ring
+        if self.phase == GamePhase::Playing && !self.players.is_empty() {
+            for bomb_index in 0..self.bomb_pos.len() as BombIndex {
+                let holder = self.bomb_pos[bomb_index as usize];
+                if self.players.contains(&holder) {
+                    continue;
+                }
+                self.handoffs.retain(|_, (bomb, _, _)| *bomb != bomb_index);
+                let to = self.hand_on(bomb_index, holder, BombMoveAction::R1);
+                effects.push(Effect::Log(GameEvent::BombPlaced {
+                    bomb: bomb_index,
+                    holder: to,
+                }));
+                self.announce_bomb(bomb_index, to, effects);
+            }
+        }
+        // the seat of a player who is gone is only needed to find who gets their bombs
+        let players_data = &self.players_data;
+        let bomb_pos = &self.bomb_pos;
+        self.players.retain_away(|player_id| {
+            players_data.contains_key(&player_id) || bomb_pos.contains(&player_id)
+        });
+
+        if self.phase == GamePhase::Playing && self.round_over {
+            effects.push(Effect::Print("Round ended...".to_string()));
+            self.round_over = false;
+            self.phase = GamePhase::Cooldown;
+            self.cooldown_end =
+                now + tokio::time::Duration::from_millis(self.config.round_cooldown_ms as u64);
+
+            // nobody is holding a bomb during the cooldown
+            self.bomb_pos.clear();
+            self.parked_bombs.clear();
+            self.handoffs.clear();
+            self.wanted_bomb_count = self.room_bomb_count;
+            effects.push(Effect::Log(GameEvent::RoundEnd));
+            effects.push(Effect::Results(
+                self.round_results(self.players_score.keys().copied()),
+            ));
+
+            let ranking = self.scoreboard();
+            let stats = self.render_stats();
+            for player_id in self.players.iter() {
+                effects.push(Effect::Send(
+                    player_id,
+                    PlayerUpdate::End(self.config.round_cooldown_ms, ranking.clone()),
+                ));
+                effects.push(Effect::Send(player_id, PlayerUpdate::Stats(stats.clone())));
+            }
+        }
+
+        if self.phase == GamePhase::Lobby
+            && !self.players.is_empty()
+            && (self.start_requested
+                || self.players.len() >= self.config.lobby_min_players as usize)
+        {
+            effects.push(Effect::Print(format!(
+                "Game started with {} players...",
+                self.players.len()
+            )));
+            self.phase = GamePhase::Playing;
+            self.start_requested = false;
+            self.round_end =
+                now + tokio::time::Duration::from_millis(self.config.round_duration_ms);
+            self.next_escalation =
+                now + tokio::time::Duration::from_millis(self.config.bomb_escalation_ms);
+            for player_id in self.players.iter() {
+                effects.push(Effect::Send(player_id, PlayerUpdate::Begin));
+            }
+
+            // every round starts from zero
+            for score in self.players_score.values_mut() {
+                *score = 0;
+            }
+            self.players_streak.clear();
+
+            // spread the bombs around the ring
+            let seats = self.players.iter().collect::<Vec<_>>();
+            self.bomb_pos = (0..self.bomb_count)
+                .map(|bomb_index| seats[bomb_index as usize % seats.len()])
+                .collect();
+            effects.push(Effect::Log(GameEvent::RoundStart {
+                holders: self.bomb_pos.clone(),
+            }));
+            for bomb_index in 0..self.bomb_count {
+                self.announce_bomb(bomb_index, self.bomb_pos[bomb_index as usize], effects);
+                self.parked_bombs.insert(bomb_index);
+            }
+        } else if self.phase == GamePhase::Lobby && self.lobby_changed {
+            let host = self.host();
+            let mut lobby = Vec::new();
+            if let Some(host) = host {
+                lobby.push(self.players_data[&host].clone());
+            }
+            for player_id in &self.join_order {
+                if Some(*player_id) != host && self.players.contains(player_id) {
+                    lobby.push(self.players_data[player_id].clone());
+                }
+            }
+            for player_id in self.players.iter() {
+                effects.push(Effect::Send(
+                    player_id,
+                    PlayerUpdate::Lobby(self.config.lobby_min_players, lobby.clone()),
+                ));
+            }
+        }
+        self.lobby_changed = false;
+
+        if self.wanted_bomb_count != self.bomb_count {
+            effects.push(Effect::Print(format!(
+                "Changing the bomb count from {} to {}...",
+                self.bomb_count, self.wanted_bomb_count
+            )));
+            effects.push(Effect::Log(GameEvent::BombCount {
+                count: self.wanted_bomb_count,
+            }));
+            if self.phase == GamePhase::Playing {
+                // answers for the removed bombs are ignored
+                let wanted_bomb_count = self.wanted_bomb_count;
+                self.handoffs
+                    .retain(|_, (bomb, _, _)| *bomb < wanted_bomb_count);
+                self.parked_bombs
+                    .retain(|bomb_index| *bomb_index < wanted_bomb_count);
+                self.bomb_pos.truncate(wanted_bomb_count as usize);
+            }
+            for player_id in self.players.iter() {
+                effects.push(Effect::Send(
+                    player_id,
+                    PlayerUpdate::BombCount(self.wanted_bomb_count),
+                ));
+            }
+            if self.phase == GamePhase::Playing {
+                // new bombs are spread around the ring like at the start of a round,
+                // or wait for a suspended player if everyone is
+                let seats = if self.players.is_empty() {
+                    self.players.seated().collect::<Vec<_>>()
+                } else {
+                    self.players.iter().collect::<Vec<_>>()
+                };
+                for bomb_index in self.bomb_count..self.wanted_bomb_count {
+                    let holder = seats[bomb_index as usize % seats.len()];
+                    self.bomb_pos.push(holder);
+                    effects.push(Effect::Log(GameEvent::BombPlaced {
+                        bomb: bomb_index,
+                        holder,
+                    }));
+                    self.announce_bomb(bomb_index, holder, effects);
+                    self.parked_bombs.insert(bomb_index);
+                }
+            }
+            self.bomb_count = self.wanted_bomb_count;
+        }
+
+        if self.phase == GamePhase::Playing && !self.paused {
+            for bomb_index in self.parked_bombs.iter().copied().collect::<Vec<_>>() {
+                let holder = self.bomb_pos[bomb_index as usize];
+                // a suspended holder gets the bomb once they resume or the ring moves it on
+                if !self.players.contains(&holder) {
+                    continue;
+                }
+                // the bomb waits where it is while nobody can see it
+                if let Some(holder) = self.pass_blind(bomb_index, holder, effects) {
+                    self.parked_bombs.remove(&bomb_index);
+                    let handoff = self.next_handoff;
+                    self.next_handoff += 1;
+                    self.handoffs.insert(handoff, (bomb_index, holder, now));
+                    effects.push(Effect::GiveBomb(holder, bomb_index, handoff));
+                    self.players_stats.entry(holder).or_default().on_received();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::scoring_policy;
+    use tokio::time::{Duration, Instant};
+
+    fn new_game(config: GameConfig) -> (GameState, Instant) {
+        let start = Instant::now();
+        let scoring = scoring_policy(config.scoring, &config);
+        (GameState::new(config, scoring, 1, start), start)
+    }
+
+    fn two_player_config() -> GameConfig {
+        GameConfig {
+            bomb_count: 1,
+            lobby_min_players: 2,
+            ..GameConfig::default()
+        }
+    }
+
+    fn join(
+        game: &mut GameState,
+        preferred_id: PreferredID,
+        name: &str,
+        now: Instant,
+    ) -> (PlayerID, Vec<Effect>) {
+        let (player_id, _, effects) = game.join(
+            preferred_id,
+            Some((name.to_string(), "#000000".to_string())),
+            false,
+            None,
+            format!("token-{name}"),
+            now,
+        );
+        (player_id, effects)
+    }
+
+    // what the effects tell one player, in order
+    fn sent(effects: &[Effect], player_id: PlayerID) -> Vec<PlayerUpdate> {
+        effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::Send(to, update) if *to == player_id => Some(update.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // the holder, bomb and handoff of every bomb handed out
+    fn given(effects: &[Effect]) -> Vec<(PlayerID, BombIndex, HandoffID)> {
+        effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::GiveBomb(holder, bomb_index, handoff) => {
+                    Some((*holder, *bomb_index, *handoff))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn board(players: &[(&str, GameScore)]) -> GameScoareboard {
+        players
+            .iter()
+            .map(|(name, score)| format!("{name}\n#000000\n{score}\n"))
+            .collect()
+    }
+
+    // Alice holds the bomb of a started game with Bob to her right
+    fn started_game(config: GameConfig) -> (GameState, Instant, PlayerID, PlayerID) {
+        let (mut game, start) = new_game(config);
+        let (alice, _) = join(&mut game, 0, "Alice", start);
+        let (bob, _) = join(&mut game, 1, "Bob", start);
+        (game, start, alice, bob)
+    }
+
+    #[test]
+    fn the_game_starts_with_enough_players_and_hands_out_the_bomb() {
+        let (mut game, start) = new_game(two_player_config());
+
+        let (alice, effects) = join(&mut game, 0, "Alice", start);
+        let alice_data = ("Alice".to_string(), "#000000".to_string());
+        assert_eq!(
+            sent(&effects, alice),
+            vec![PlayerUpdate::Lobby(2, vec![alice_data.clone()])]
+        );
+        assert!(given(&effects).is_empty());
+
+        let (bob, effects) = join(&mut game, 1, "Bob", start);
+        assert_eq!(sent(&effects, alice), vec![PlayerUpdate::Begin]);
+        assert_eq!(
+            sent(&effects, bob),
+            vec![
+                PlayerUpdate::Begin,
+                PlayerUpdate::BombMoved(0, BombPosition::L)
+            ]
+        );
+        assert_eq!(given(&effects), vec![(alice, 0, 0)]);
+        assert_eq!(game.scoreboard(), board(&[("Bob", 0), ("Alice", 0)]));
+    }
+
+    #[test]
+    fn passing_scores_the_time_held_and_moves_the_bomb() {
+        let (mut game, start, alice, bob) = started_game(two_player_config());
+
+        let effects = game.pass(0, BombMoveAction::R1, start + Duration::from_millis(300));
+        assert_eq!(
+            sent(&effects, alice),
+            vec![PlayerUpdate::BombMoved(0, BombPosition::R)]
+        );
+        assert!(sent(&effects, bob).is_empty());
+        assert_eq!(given(&effects), vec![(bob, 0, 1)]);
+        assert!(effects.contains(&Effect::Log(GameEvent::Pass {
+            bomb: 0,
+            from: alice,
+            to: bob,
+            action: BombMoveAction::R1,
+            ms: 300,
+        })));
+        assert_eq!(game.scoreboard(), board(&[("Alice", 3800), ("Bob", 0)]));
+    }
+
+    #[test]
+    fn the_bomb_moves_around_the_ring() {
+        let (mut game, start) = new_game(GameConfig {
+            bomb_count: 1,
+            lobby_min_players: 6,
+            ..GameConfig::default()
+        });
+        let players = ["A", "B", "C", "D", "E", "F"]
+            .iter()
+            .enumerate()
+            .map(|(seat, name)| join(&mut game, seat as PreferredID, name, start).0)
+            .collect::<Vec<_>>();
+
+        // from seat 0: R1 to 1, R2 to 3, L1 to 2, L3 wraps around to 5
+        let mut handoff = 0;
+        for (action, to) in [
+            (BombMoveAction::R1, 1),
+            (BombMoveAction::R2, 3),
+            (BombMoveAction::L1, 2),
+            (BombMoveAction::L3, 5),
+        ] {
+            let effects = game.pass(handoff, action, start);
+            handoff += 1;
+            assert_eq!(given(&effects), vec![(players[to], 0, handoff)]);
+        }
+    }
+
+    #[test]
+    fn holding_the_bomb_too_long_costs_the_penalty_and_passes_it_right() {
+        let (mut game, start, alice, bob) = started_game(two_player_config());
+        game.pass(0, BombMoveAction::R1, start);
+        game.pass(1, BombMoveAction::R1, start + Duration::from_millis(100));
+
+        let timed_out_at = start + Duration::from_millis(6100);
+        let effects = game.timeout(2, timed_out_at);
+        assert_eq!(given(&effects), vec![(bob, 0, 3)]);
+        assert!(effects.contains(&Effect::Log(GameEvent::Timeout {
+            bomb: 0,
+            player: alice,
+            to: bob,
+            ms: 6000,
+        })));
+        assert_eq!(game.scoreboard(), board(&[("Bob", 4000), ("Alice", 0)]));
+
+        // the answer came too late, the bomb isn't Alice's anymore
+        let effects = game.pass(2, BombMoveAction::R1, timed_out_at);
+        assert!(given(&effects).is_empty());
+        assert_eq!(game.scoreboard(), board(&[("Bob", 4000), ("Alice", 0)]));
+    }
+
+    #[test]
+    fn timeouts_while_paused_keep_the_bomb_without_a_penalty() {
+        let (mut game, start, alice, bob) = started_game(two_player_config());
+        game.pass(0, BombMoveAction::R1, start);
+        game.pause(start + Duration::from_secs(1));
+
+        let effects = game.timeout(1, start + Duration::from_secs(6));
+        assert!(given(&effects).is_empty());
+        assert!(sent(&effects, alice).is_empty());
+        assert_eq!(game.scoreboard(), board(&[("Alice", 4100), ("Bob", 0)]));
+
+        // Bob gets the bomb back with a new AFK timeout
+        let resumed_at = start + Duration::from_secs(10);
+        let effects = game.unpause(resumed_at);
+        assert_eq!(given(&effects), vec![(bob, 0, 2)]);
+        assert_eq!(game.room_info().players[1].stats.timeouts, 0);
+
+        // an AFK timeout that ran through the pause doesn't count either
+        game.pass(2, BombMoveAction::R1, resumed_at);
+        game.pause(resumed_at + Duration::from_secs(1));
+        game.unpause(resumed_at + Duration::from_secs(2));
+        let effects = game.timeout(3, resumed_at + Duration::from_secs(6));
+        assert_eq!(given(&effects), vec![(alice, 0, 4)]);
+        assert_eq!(game.scoreboard(), board(&[("Bob", 4100), ("Alice", 4100)]));
+
+        let effects = game.timeout(4, resumed_at + Duration::from_secs(12));
+        assert_eq!(given(&effects), vec![(bob, 0, 5)]);
+        assert_eq!(game.scoreboard(), board(&[("Bob", 4100), ("Alice", 0)]));
+    }
+
+    #[test]
+    fn added_bombs_skip_players_who_cant_see_them() {
+        let (mut game, start) = new_game(GameConfig {
+            bomb_count: 1,
+            lobby_min_players: 3,
+            ..GameConfig::default()
+        });
+        let (alice, _) = join(&mut game, 0, "Alice", start);
+        // Bob's client only knows about the first bomb
+        let (bob, _, _) = game.join(1, None, false, Some(1), "token-Bob".to_string(), start);
+        let (carol, _) = join(&mut game, 2, "Carol", start);
+
+        // the new bomb starts at Bob's seat but goes on to Carol without costing him anything
+        let effects = game.set_bomb_count(2, start);
+        assert_eq!(given(&effects), vec![(carol, 1, 1)]);
+        assert!(effects.contains(&Effect::Log(GameEvent::BombPlaced {
+            bomb: 1,
+            holder: carol
+        })));
+        let bob_info = &game.room_info().players[1];
+        assert_eq!((bob_info.id, bob_info.stats.timeouts), (bob, 0));
+
+        // passed to Bob it goes on right away too
+        let effects = game.pass(1, BombMoveAction::L1, start);
+        assert_eq!(given(&effects), vec![(carol, 1, 2)]);
+        let effects = game.pass(0, BombMoveAction::R1, start);
+        assert_eq!(given(&effects), vec![(bob, 0, 3)]);
+
+        // with nobody able to see it the bomb waits
+        game.leave(alice, start);
+        game.leave(carol, start);
+        assert!(game.room_info().players[0].bombs.contains(&1));
+        assert!(!game
+            .handoffs
+            .values()
+            .any(|(bomb_index, _, _)| *bomb_index == 1));
+    }
+
+    #[test]
+    fn leaving_with_the_bomb_passes_it_on() {
+        let (mut game, start) = new_game(GameConfig {
+            bomb_count: 1,
+            lobby_min_players: 3,
+            ..GameConfig::default()
+        });
+        let (alice, _) = join(&mut game, 0, "Alice", start);
+        let (bob, _) = join(&mut game, 1, "Bob", start);
+        let (carol, _) = join(&mut game, 2, "Carol", start);
+        game.pass(0, BombMoveAction::R1, start);
+
+        let effects = game.leave(bob, start);
+        assert!(effects.contains(&Effect::Results(vec![("Bob".to_string(), 0)])));
+        assert_eq!(given(&effects), vec![(carol, 0, 2)]);
+        assert_eq!(
+            sent(&effects, alice),
+            vec![PlayerUpdate::BombMoved(0, BombPosition::R)]
+        );
+        assert_eq!(game.scoreboard(), board(&[("Alice", 4100), ("Carol", 0)]));
+
+        // the holder's answer is dropped with their connection
+        let effects = game.drop_handoff(1, start);
+        assert!(given(&effects).is_empty());
+    }
+
+    #[test]
+    fn disconnected_players_keep_their_seat_until_the_grace_period_ends() {
+        let (mut game, start, alice, bob) = started_game(GameConfig {
+            resume_grace_ms: 1000,
+            ..two_player_config()
+        });
+
+        // the bomb moves on without Alice but she keeps her seat and score
+        let effects = game.disconnect(alice, start);
+        assert_eq!(given(&effects), vec![(bob, 0, 1)]);
+        assert_eq!(game.next_deadline(), Some(start + Duration::from_secs(1)));
+        assert!(game
+            .tick(start + Duration::from_millis(999))
+            .iter()
+            .all(|effect| matches!(effect, Effect::Print(_))));
+        assert_eq!(game.room_info().players.len(), 2);
+
+        let effects = game.tick(start + Duration::from_secs(1));
+        assert!(effects.contains(&Effect::Log(GameEvent::Leave { player: alice })));
+        assert_eq!(game.room_info().players.len(), 1);
+        assert!(game
+            .resume(&"token-Alice".to_string(), None, start)
+            .is_none());
+    }
+
+    #[test]
+    fn resuming_players_get_their_seat_back() {
+        let (mut game, start, alice, bob) = started_game(GameConfig {
+            resume_grace_ms: 1000,
+            ..two_player_config()
+        });
+        game.disconnect(alice, start);
+
+        let (player_id, _, effects) = game
+            .resume(
+                &"token-Alice".to_string(),
+                None,
+                start + Duration::from_millis(500),
+            )
+            .unwrap();
+        assert_eq!(player_id, alice);
+        assert_eq!(
+            sent(&effects, alice),
+            vec![
+                PlayerUpdate::Begin,
+                PlayerUpdate::BombMoved(0, BombPosition::R)
+            ]
+        );
+        assert_eq!(game.next_deadline(), None);
+        assert!(game
+            .room_info()
+            .players
+            .iter()
+            .all(|player| player.connected));
+        assert_eq!(game.room_info().players[1].id, bob);
+    }
+
+    #[test]
+    fn the_round_ends_at_the_target_score() {
+        let (mut game, start, alice, bob) = started_game(GameConfig {
+            round_target_score: 4000,
+            round_cooldown_ms: 2000,
+            ..two_player_config()
+        });
+
+        let effects = game.pass(0, BombMoveAction::R1, start);
+        assert!(given(&effects).is_empty());
+        assert!(effects.contains(&Effect::Results(vec![
+            ("Alice".to_string(), 4100),
+            ("Bob".to_string(), 0)
+        ])));
+        let ranking = board(&[("Alice", 4100), ("Bob", 0)]);
+        // Alice still hears where the bomb went before the round ends
+        for (player_id, skipped) in [(alice, 1), (bob, 0)] {
+            let updates = &sent(&effects, player_id)[skipped..];
+            assert_eq!(updates[0], PlayerUpdate::End(2000, ranking.clone()));
+            assert!(matches!(updates[1], PlayerUpdate::Stats(_)));
+        }
+        assert_eq!(game.room_info().phase, GamePhase::Cooldown);
+
+        // the next round starts from zero once the cooldown is over
+        let cooldown_end = start + Duration::from_secs(2);
+        assert_eq!(game.next_deadline(), Some(cooldown_end));
+        let effects = game.tick(cooldown_end);
+        assert_eq!(sent(&effects, alice), vec![PlayerUpdate::Begin]);
+        assert_eq!(given(&effects), vec![(alice, 0, 1)]);
+        assert_eq!(game.scoreboard(), board(&[("Bob", 0), ("Alice", 0)]));
+    }
+
+    #[test]
+    fn players_with_a_random_name_stay_off_the_leaderboard() {
+        let (mut game, start) = new_game(GameConfig {
+            round_target_score: 4000,
+            ..two_player_config()
+        });
+        join(&mut game, 0, "Alice", start);
+        let (bob, _, _) = game.join(1, None, false, None, "token-Bob".to_string(), start);
+        // asks for a name that's taken
+        game.join(
+            2,
+            Some(("Alice".to_string(), "#000000".to_string())),
+            false,
+            None,
+            "token-Carol".to_string(),
+            start,
+        );
+
+        let effects = game.leave(bob, start);
+        assert!(effects.contains(&Effect::Results(Vec::new())));
+        let effects = game.pass(0, BombMoveAction::R1, start);
+        assert!(effects.contains(&Effect::Results(vec![("Alice".to_string(), 4100)])));
+    }
+
+    #[test]
+    fn names_used_in_other_rooms_are_not_given_out() {
+        let (mut game, start) = new_game(two_player_config());
+        game.reserve_names(["Alice".to_string()].into());
+        let (_, (name, _), _) = game.join(
+            0,
+            Some(("Alice".to_string(), "#000000".to_string())),
+            false,
+            None,
+            "token-Alice".to_string(),
+            start,
+        );
+        assert_ne!(name, "Alice");
+        assert_eq!(game.player_names(), [name].into());
+    }
+
+    #[test]
+    fn the_round_ends_when_its_time_is_up() {
+        let (mut game, start, _, _) = started_game(GameConfig {
+            round_duration_ms: 10000,
+            ..two_player_config()
+        });
+        let round_end = start + Duration::from_secs(10);
+        assert_eq!(game.next_deadline(), Some(round_end));
+
+        game.tick(round_end - Duration::from_millis(1));
+        assert_eq!(game.room_info().phase, GamePhase::Playing);
+        let effects = game.tick(round_end);
+        assert!(effects.contains(&Effect::Log(GameEvent::RoundEnd)));
+        assert_eq!(game.room_info().phase, GamePhase::Cooldown);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_names() {
+        let names = |seed| {
+            let config = two_player_config();
+            let scoring = scoring_policy(config.scoring, &config);
+            let mut game = GameState::new(config, scoring, seed, Instant::now());
+            (0..3)
+                .map(|preferred_id| {
+                    game.join(
+                        preferred_id,
+                        None,
+                        false,
+                        None,
+                        preferred_id.to_string(),
+                        Instant::now(),
+                    )
+                    .1
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(7), names(7));
+        assert_ne!(names(7), names(8));
+    }
+}
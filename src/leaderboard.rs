@@ -0,0 +1,220 @@
+use crate::packet::{GameScore, PlayerName};
+
+// daily leaderboards older than this many days are dropped when a new day starts
+pub const LEADERBOARD_DAYS: usize = 30;
+
+// a day in UTC, formatted as YYYY-MM-DD
+pub type LeaderboardDay = String;
+
+// What a player reached in every round they finished, players are told apart by their name.
+// Names aren't accounts: nobody can take a name that's in use in any room, but anyone can
+// play under a name once its player is gone and add to their record.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlayerRecord {
+    pub best_score: GameScore,
+    pub total_score: u64,
+    pub rounds: u32,
+}
+
+impl PlayerRecord {
+    fn add(&mut self, score: GameScore) {
+        self.best_score = self.best_score.max(score);
+        self.total_score += score as u64;
+        self.rounds += 1;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct LeaderboardEntry {
+    pub name: PlayerName,
+    #[serde(flatten)]
+    pub record: PlayerRecord,
+}
+
+#[derive(Debug)]
+pub enum LeaderboardError {
+    Read(std::path::PathBuf, std::io::Error),
+    Parse(std::path::PathBuf, serde_json::Error),
+    Write(std::path::PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for LeaderboardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(path, err) => write!(f, "failed reading {}: {}", path.display(), err),
+            Self::Parse(path, err) => write!(f, "failed parsing {}: {}", path.display(), err),
+            Self::Write(path, err) => write!(f, "failed writing {}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for LeaderboardError {}
+
+// Scores of every round across all games, all time and per day.
+// Stored as a single JSON file that is rewritten after every change.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Leaderboard {
+    all_time: std::collections::BTreeMap<PlayerName, PlayerRecord>,
+    daily: std::collections::BTreeMap<
+        LeaderboardDay,
+        std::collections::BTreeMap<PlayerName, PlayerRecord>,
+    >,
+}
+
+impl Leaderboard {
+    // a missing file is an empty leaderboard, it's created on the first save
+    pub fn load(path: &std::path::Path) -> Result<Leaderboard, LeaderboardError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|err| LeaderboardError::Parse(path.to_path_buf(), err)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Leaderboard::default()),
+            Err(err) => Err(LeaderboardError::Read(path.to_path_buf(), err)),
+        }
+    }
+
+    // writes to a temporary file first so a crash never leaves half a leaderboard behind
+    pub fn save(&self, path: &std::path::Path) -> Result<(), LeaderboardError> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let temporary = std::path::PathBuf::from(temporary);
+        std::fs::write(&temporary, serde_json::to_string(self).unwrap())
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .map_err(|err| LeaderboardError::Write(path.to_path_buf(), err))
+    }
+
+    pub fn record(&mut self, day: &str, name: &str, score: GameScore) {
+        self.all_time
+            .entry(name.to_string())
+            .or_default()
+            .add(score);
+        if !self.daily.contains_key(day) {
+            // days are sorted, so the oldest ones come first
+            while self.daily.len() >= LEADERBOARD_DAYS {
+                self.daily.pop_first();
+            }
+        }
+        self.daily
+            .entry(day.to_string())
+            .or_default()
+            .entry(name.to_string())
+            .or_default()
+            .add(score);
+    }
+
+    // the players with the best single round first
+    pub fn all_time(&self, limit: usize) -> Vec<LeaderboardEntry> {
+        ranking(&self.all_time, limit)
+    }
+
+    pub fn daily(&self, day: &str, limit: usize) -> Vec<LeaderboardEntry> {
+        self.daily
+            .get(day)
+            .map_or_else(Vec::new, |records| ranking(records, limit))
+    }
+}
+
+fn ranking(
+    records: &std::collections::BTreeMap<PlayerName, PlayerRecord>,
+    limit: usize,
+) -> Vec<LeaderboardEntry> {
+    let mut entries = records
+        .iter()
+        .map(|(name, record)| LeaderboardEntry {
+            name: name.clone(),
+            record: *record,
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| {
+        b.record
+            .best_score
+            .cmp(&a.record.best_score)
+            .then(b.record.total_score.cmp(&a.record.total_score))
+            .then(a.name.cmp(&b.name))
+    });
+    entries.truncate(limit);
+    entries
+}
+
+// the UTC day of the time, e.g. 2023-03-14
+pub fn leaderboard_day(time: std::time::SystemTime) -> LeaderboardDay {
+    let days = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400;
+    // days since 1970-01-01 to a date, counting from 0000-03-01 so leap days end the year
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day_at(unix_secs: u64) -> LeaderboardDay {
+        leaderboard_day(std::time::UNIX_EPOCH + std::time::Duration::from_secs(unix_secs))
+    }
+
+    #[test]
+    fn the_epoch_is_the_first_day() {
+        assert_eq!(day_at(0), "1970-01-01");
+        assert_eq!(day_at(86399), "1970-01-01");
+        assert_eq!(day_at(86400), "1970-01-02");
+    }
+
+    #[test]
+    fn leap_days_are_counted() {
+        assert_eq!(day_at(951_782_400), "2000-02-29");
+        assert_eq!(day_at(951_868_800), "2000-03-01");
+        assert_eq!(day_at(1_709_164_800), "2024-02-29");
+        // 2100 isn't a leap year
+        assert_eq!(day_at(4_107_456_000), "2100-02-28");
+        assert_eq!(day_at(4_107_542_400), "2100-03-01");
+    }
+
+    #[test]
+    fn days_roll_over_into_the_next_year() {
+        assert_eq!(day_at(1_704_067_199), "2023-12-31");
+        assert_eq!(day_at(1_704_067_200), "2024-01-01");
+    }
+
+    #[test]
+    fn times_before_the_epoch_count_as_the_epoch() {
+        let before = std::time::UNIX_EPOCH - std::time::Duration::from_secs(86400);
+        assert_eq!(leaderboard_day(before), "1970-01-01");
+    }
+
+    #[test]
+    fn records_are_kept_by_name() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.record("2024-01-01", "Alice", 300);
+        leaderboard.record("2024-01-01", "Alice", 100);
+        leaderboard.record("2024-01-02", "Bob", 200);
+        let all_time = leaderboard.all_time(10);
+        assert_eq!(all_time[0].name, "Alice");
+        assert_eq!(
+            all_time[0].record,
+            PlayerRecord {
+                best_score: 300,
+                total_score: 400,
+                rounds: 2
+            }
+        );
+        assert_eq!(leaderboard.daily("2024-01-02", 10)[0].name, "Bob");
+        assert!(leaderboard.daily("2024-01-03", 10).is_empty());
+    }
+}
@@ -1 +1,12 @@
+pub mod bot;
+pub mod client;
+pub mod clock;
+pub mod config;
+pub mod events;
+pub mod game;
+pub mod leaderboard;
 pub mod packet;
+pub mod ring;
+pub mod scoring;
+pub mod server;
+pub mod stats;
@@ -1,4 +1,100 @@
-#[derive(Debug, Clone)]
+// Why a packet from the client couldn't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketError {
+    LineCount,
+    WhitespaceCount,
+    BadNumber,
+    BadAction,
+    BadPosition,
+    BadResumeToken,
+    BadCapability,
+    BadPhase,
+    UnknownType,
+    BadJson,
+}
+
+impl std::fmt::Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PacketError::LineCount => "Packet has unexpected amount of lines",
+                PacketError::WhitespaceCount => "Packet has unexpected amount of whitespaces",
+                PacketError::BadNumber => "Packet contains a bad number",
+                PacketError::BadAction => "Packet contains a bad action type",
+                PacketError::BadPosition => "Packet contains a bad bomb position",
+                PacketError::BadResumeToken => "Packet contains a bad resume token",
+                PacketError::BadCapability => "Packet contains a bad capability name",
+                PacketError::BadPhase => "Packet contains a bad game phase",
+                PacketError::UnknownType => "Packet contained a unexpected type identifier",
+                PacketError::BadJson => "Packet is not a valid JSON packet",
+            }
+        )
+    }
+}
+
+impl std::error::Error for PacketError {}
+
+// Sent to the client with the ERROR packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // the packet couldn't be parsed
+    BadPacket = 1,
+    // the packet is valid but not expected at this point (e.g. MOVE before OLLEH)
+    UnexpectedPacket = 2,
+    // binary or other non-text websocket messages
+    NotText = 3,
+    // the bomb index of a MOVE isn't less than the bomb count
+    BadBombIndex = 4,
+    // a MOVE for a bomb the player isn't holding (anymore), the connection stays open
+    BombNotHeld = 5,
+    // the client took too long to answer HELLO
+    OllehTimeout = 6,
+    // the resume token is unknown or the player isn't disconnected
+    ResumeRejected = 7,
+    // the protocol version in OLLEH/RESUME isn't one the server can talk
+    UnsupportedVersion = 8,
+}
+
+impl ErrorCode {
+    pub fn from_code(code: u32) -> Option<ErrorCode> {
+        match code {
+            1 => Some(ErrorCode::BadPacket),
+            2 => Some(ErrorCode::UnexpectedPacket),
+            3 => Some(ErrorCode::NotText),
+            4 => Some(ErrorCode::BadBombIndex),
+            5 => Some(ErrorCode::BombNotHeld),
+            6 => Some(ErrorCode::OllehTimeout),
+            7 => Some(ErrorCode::ResumeRejected),
+            8 => Some(ErrorCode::UnsupportedVersion),
+            _ => None,
+        }
+    }
+
+    // whether the server closes the connection after sending the error
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, ErrorCode::BombNotHeld)
+    }
+
+    // websocket close code (RFC 6455) the connection is closed with
+    pub fn close_code(&self) -> u16 {
+        use axum::extract::ws::close_code;
+        match self {
+            ErrorCode::BadPacket
+            | ErrorCode::UnexpectedPacket
+            | ErrorCode::BadBombIndex
+            | ErrorCode::UnsupportedVersion => close_code::PROTOCOL,
+            ErrorCode::NotText => close_code::UNSUPPORTED,
+            ErrorCode::BombNotHeld | ErrorCode::OllehTimeout | ErrorCode::ResumeRejected => {
+                close_code::POLICY
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum BombMoveAction {
     L3,
     L1,
@@ -7,7 +103,7 @@ pub enum BombMoveAction {
 }
 
 impl std::str::FromStr for BombMoveAction {
-    type Err = ();
+    type Err = PacketError;
 
     fn from_str(input: &str) -> Result<BombMoveAction, Self::Err> {
         match input {
@@ -15,7 +111,7 @@ impl std::str::FromStr for BombMoveAction {
             "L1" => Ok(BombMoveAction::L1),
             "R1" => Ok(BombMoveAction::R1),
             "R2" => Ok(BombMoveAction::R2),
-            _ => Err(()),
+            _ => Err(PacketError::BadAction),
         }
     }
 }
@@ -46,7 +142,7 @@ impl rand::distributions::Distribution<BombMoveAction> for rand::distributions::
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum BombPosition {
     L,
     X,
@@ -54,14 +150,14 @@ pub enum BombPosition {
 }
 
 impl std::str::FromStr for BombPosition {
-    type Err = ();
+    type Err = PacketError;
 
     fn from_str(input: &str) -> Result<BombPosition, Self::Err> {
         match input {
             "L" => Ok(BombPosition::L),
             "X" => Ok(BombPosition::X),
             "R" => Ok(BombPosition::R),
-            _ => Err(()),
+            _ => Err(PacketError::BadPosition),
         }
     }
 }
@@ -80,89 +176,619 @@ impl std::fmt::Display for BombPosition {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GamePhase {
+    // waiting for enough players or the host to start the round
+    Lobby,
+    Playing,
+    // the round ended, the game goes back to the lobby after the cooldown
+    Cooldown,
+}
+
+impl std::str::FromStr for GamePhase {
+    type Err = PacketError;
+
+    fn from_str(input: &str) -> Result<GamePhase, Self::Err> {
+        match input {
+            "lobby" => Ok(GamePhase::Lobby),
+            "playing" => Ok(GamePhase::Playing),
+            "cooldown" => Ok(GamePhase::Cooldown),
+            _ => Err(PacketError::BadPhase),
+        }
+    }
+}
+
+impl std::fmt::Display for GamePhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                GamePhase::Lobby => "lobby",
+                GamePhase::Playing => "playing",
+                GamePhase::Cooldown => "cooldown",
+            }
+        )
+    }
+}
+
 pub type BombCount = u32;
 pub type BombIndex = u32;
 pub type PlayerID = u32;
 pub type PreferredID = u32;
 pub type GameScore = u32;
+pub type PlayerCount = u32;
 pub type PlayerName = String;
 pub type PlayerColor = String;
 pub type PlayerData = (PlayerName, PlayerColor);
 pub type GameScoareboard = String;
+// given to a player with the NAME packet, lets a new connection take the player's seat back
+pub type ResumeToken = String;
 
-#[derive(Debug, Clone)]
+// What a player did since joining the game, sent with the STATS packet.
+// Reaction times are how long the player held a bomb before passing it, in milliseconds,
+// and are None until the first pass.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlayerStats {
+    pub passes: u32,
+    pub timeouts: u32,
+    pub bombs_received: u32,
+    pub best_reaction_ms: Option<u32>,
+    pub mean_reaction_ms: Option<u32>,
+    pub median_reaction_ms: Option<u32>,
+    // counts bombs taken away for being AFK too
+    pub longest_hold_ms: u32,
+}
+
+// PLRNAME = 1*32(ALPHA/DIGIT)
+pub fn valid_player_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 32 && name.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+pub type ProtocolVersion = u32;
+pub type Capability = String;
+// a protocol version and the capabilities a side understands, sent in HELLO and OLLEH/RESUME
+pub type ProtocolSupport = (ProtocolVersion, Vec<Capability>);
+
+// version 1 is the protocol before versions were added, a client that sends no version talks it
+pub const PROTOCOL_VERSION: ProtocolVersion = 2;
+pub const MIN_PROTOCOL_VERSION: ProtocolVersion = 1;
+
+// LOBBY, BEGIN and START
+pub const CAP_LOBBY: &str = "lobby";
+// END
+pub const CAP_ROUNDS: &str = "rounds";
+// the resume token in NAME, and RESUME
+pub const CAP_RESUME: &str = "resume";
+// ERROR
+pub const CAP_ERROR: &str = "error";
+// SHUTDOWN
+pub const CAP_SHUTDOWN: &str = "shutdown";
+// BOMBS
+pub const CAP_BOMBS: &str = "bombs";
+// STATS (and asking for it)
+pub const CAP_STATS: &str = "stats";
+pub const SERVER_CAPABILITIES: [&str; 7] = [
+    CAP_LOBBY,
+    CAP_ROUNDS,
+    CAP_RESUME,
+    CAP_ERROR,
+    CAP_SHUTDOWN,
+    CAP_BOMBS,
+    CAP_STATS,
+];
+
+// CAP = 1*(ALPHA/DIGIT/"-"/"_")
+pub fn valid_capability(capability: &str) -> bool {
+    !capability.is_empty()
+        && capability
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// What both sides agreed on in the handshake, packets of capabilities the client
+// doesn't know about aren't sent to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiated {
+    pub version: ProtocolVersion,
+    pub capabilities: Vec<Capability>,
+}
+
+impl Negotiated {
+    // everything the server can do, used until the client answers HELLO
+    pub fn latest() -> Negotiated {
+        Negotiated {
+            version: PROTOCOL_VERSION,
+            capabilities: SERVER_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    // the client's offer is None when it didn't send a version (version 1)
+    // capabilities the server doesn't know are ignored
+    pub fn negotiate(client: Option<&ProtocolSupport>) -> Result<Negotiated, String> {
+        let (version, capabilities) = match client {
+            None => (1, &Vec::new()),
+            Some((version, capabilities)) => (*version, capabilities),
+        };
+        if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+            return Err(format!(
+                "Protocol version {} is not supported, the server talks {} to {}",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ));
+        }
+        Ok(Negotiated {
+            version,
+            capabilities: SERVER_CAPABILITIES
+                .iter()
+                .filter(|c| version >= 2 && capabilities.iter().any(|offered| offered == *c))
+                .map(|c| c.to_string())
+                .collect(),
+        })
+    }
+
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+// VERSIONLINE = VERSION *(" " CAP)
+fn parse_protocol_support(line: &str) -> Result<ProtocolSupport, PacketError> {
+    let mut split = line.split(" ");
+    let version = match split.next().unwrap().parse() {
+        Ok(version) => version,
+        Err(_) => return Err(PacketError::BadNumber),
+    };
+    let mut capabilities = Vec::new();
+    for capability in split {
+        if capability.is_empty() {
+            return Err(PacketError::WhitespaceCount);
+        }
+        if !valid_capability(capability) {
+            return Err(PacketError::BadCapability);
+        }
+        capabilities.push(capability.to_string());
+    }
+    Ok((version, capabilities))
+}
+
+fn write_protocol_support(
+    f: &mut std::fmt::Formatter<'_>,
+    (version, capabilities): &ProtocolSupport,
+) -> std::fmt::Result {
+    write!(f, "{}", version)?;
+    for capability in capabilities {
+        write!(f, " {}", capability)?;
+    }
+    Ok(())
+}
+
+// TOKEN = 32HEXDIG
+pub fn valid_resume_token(token: &str) -> bool {
+    token.len() == 32 && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// PLRCLR = "#" 6HEXDIG
+pub fn valid_player_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// In JSON a packet is {"type": <lowercase packet name>, "data": <fields as an array>}
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "data")]
 pub enum ClientPacket {
-    PacketOLLEH(PreferredID),
+    // the protocol the client talks (None for version 1),
+    // the name and color are only what the client asks for, the server checks them itself
+    #[serde(rename = "olleh")]
+    PacketOLLEH(PreferredID, Option<ProtocolSupport>, Option<PlayerData>),
+    #[serde(rename = "move")]
     PacketMOVE(BombIndex, BombMoveAction),
+    #[serde(rename = "start")]
+    PacketSTART,
+    #[serde(rename = "resume")]
+    PacketRESUME(ResumeToken, Option<ProtocolSupport>),
+    // asks for a STATS packet
+    #[serde(rename = "stats")]
+    PacketSTATS,
 }
 
 impl std::str::FromStr for ClientPacket {
-    type Err = &'static str;
+    type Err = PacketError;
 
     fn from_str(input: &str) -> Result<ClientPacket, Self::Err> {
-        if input.split("\n").count() != 2 {
-            return Err("Packet has unexpected amount of lines");
-        }
-
         let split: Vec<&str> = input.split("\n").collect();
 
         match split[0] {
             "olleh" => {
+                // the version line is optional, and so are the name and color after it
+                if !(2..=5).contains(&split.len()) {
+                    return Err(PacketError::LineCount);
+                }
+
                 let preferred_id = match split[1].parse() {
                     Ok(id) => id,
-                    Err(_) => return Err("Packet contains a bad number"),
+                    Err(_) => return Err(PacketError::BadNumber),
+                };
+                let (protocol, data_lines) = if split.len() % 2 == 1 {
+                    (Some(parse_protocol_support(split[2])?), &split[3..])
+                } else {
+                    (None, &split[2..])
                 };
-                Ok(ClientPacket::PacketOLLEH(preferred_id))
+                let player_data = if data_lines.len() == 2 {
+                    Some((data_lines[0].to_string(), data_lines[1].to_string()))
+                } else {
+                    None
+                };
+                Ok(ClientPacket::PacketOLLEH(
+                    preferred_id,
+                    protocol,
+                    player_data,
+                ))
             }
             "move" => {
                 if input.split("\n").count() != 2 {
-                    return Err("Packet has unexpected amount of lines");
+                    return Err(PacketError::LineCount);
                 }
 
                 if split[1].split(" ").count() != 2 {
-                    return Err("Packet has unexpected amount of whitespaces");
+                    return Err(PacketError::WhitespaceCount);
                 }
 
                 let info_split: Vec<&str> = split[1].split(" ").collect();
 
                 let bomb_index = match info_split[0].parse() {
                     Ok(index) => index,
-                    Err(_) => return Err("Packet contains a bad number"),
+                    Err(_) => return Err(PacketError::BadNumber),
                 };
 
-                let bomb_move_action = match info_split[1].parse() {
-                    Ok(id) => id,
-                    Err(_) => return Err("Packet contains a bad action type"),
-                };
+                let bomb_move_action = info_split[1].parse()?;
 
                 Ok(ClientPacket::PacketMOVE(bomb_index, bomb_move_action))
             }
-            _ => Err("Packet contained a unexpected type identifier"),
+            "start" => {
+                if split.len() != 1 {
+                    return Err(PacketError::LineCount);
+                }
+
+                Ok(ClientPacket::PacketSTART)
+            }
+            "stats" => {
+                if split.len() != 1 {
+                    return Err(PacketError::LineCount);
+                }
+
+                Ok(ClientPacket::PacketSTATS)
+            }
+            "resume" => {
+                if split.len() != 2 && split.len() != 3 {
+                    return Err(PacketError::LineCount);
+                }
+
+                if !valid_resume_token(split[1]) {
+                    return Err(PacketError::BadResumeToken);
+                }
+
+                let protocol = match split.get(2) {
+                    Some(line) => Some(parse_protocol_support(line)?),
+                    None => None,
+                };
+                Ok(ClientPacket::PacketRESUME(split[1].to_string(), protocol))
+            }
+            _ => Err(PacketError::UnknownType),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+impl std::fmt::Display for ClientPacket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PacketOLLEH(preferred_id, protocol, player_data) => {
+                write!(f, "olleh\n{}", preferred_id)?;
+                if let Some(protocol) = protocol {
+                    writeln!(f)?;
+                    write_protocol_support(f, protocol)?;
+                }
+                if let Some((player_name, player_color)) = player_data {
+                    write!(f, "\n{}\n{}", player_name, player_color)?;
+                }
+                Ok(())
+            }
+            Self::PacketMOVE(bomb_index, action) => write!(f, "move\n{} {}", bomb_index, action),
+            Self::PacketSTART => write!(f, "start"),
+            Self::PacketSTATS => write!(f, "stats"),
+            Self::PacketRESUME(resume_token, protocol) => {
+                write!(f, "resume\n{}", resume_token)?;
+                if let Some(protocol) = protocol {
+                    writeln!(f)?;
+                    write_protocol_support(f, protocol)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Same JSON layout as ClientPacket, scoreboards become lists of {name, color, score}
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "data")]
 pub enum ServerPacket {
-    PacketHELLO(BombCount),
-    PacketNAME(PlayerName, PlayerColor),
+    // bomb count, and the newest protocol version and the capabilities of the server
+    #[serde(rename = "hello")]
+    PacketHELLO(BombCount, ProtocolVersion, Vec<Capability>),
+    // the resume token is left out for clients without the resume capability
+    #[serde(rename = "name")]
+    PacketNAME(PlayerName, PlayerColor, Option<ResumeToken>),
+    #[serde(rename = "status")]
     PacketSTATUS(BombIndex, BombPosition),
-    PacketBOARD(GameScoareboard),
+    #[serde(rename = "board")]
+    PacketBOARD(#[serde(with = "scoreboard_entries")] GameScoareboard),
+    // players needed for the game to start by itself, and the players waiting (host first)
+    #[serde(rename = "lobby")]
+    PacketLOBBY(PlayerCount, Vec<PlayerData>),
+    #[serde(rename = "begin")]
+    PacketBEGIN,
+    // milliseconds until the next round, and the final ranking of the round
+    #[serde(rename = "end")]
+    PacketEND(u32, #[serde(with = "scoreboard_entries")] GameScoareboard),
+    // what went wrong with the last packet of the client, the reason is a single line for humans
+    #[serde(rename = "error")]
+    PacketERROR(ErrorCode, String),
+    // the server is shutting down, with the final scoreboard of the game
+    #[serde(rename = "shutdown")]
+    PacketSHUTDOWN(#[serde(with = "scoreboard_entries")] GameScoareboard),
+    // the new bomb count of the game, bombs are added at or taken away from the end
+    #[serde(rename = "bombs")]
+    PacketBOMBS(BombCount),
+    // the stats of every player in seat order
+    #[serde(rename = "stats")]
+    PacketSTATS(Vec<(PlayerName, PlayerStats)>),
+    // only sent to spectators: the game phase, the holder of every bomb (empty outside of rounds),
+    // and every player in ring order with their score
+    #[serde(rename = "view")]
+    PacketVIEW(
+        GamePhase,
+        Vec<PlayerName>,
+        #[serde(with = "scoreboard_entries")] GameScoareboard,
+    ),
+}
+
+// the text scoreboard is "name\ncolor\nscore\n" for every player, JSON gets a list instead
+mod scoreboard_entries {
+    use super::{GameScoareboard, GameScore, PlayerColor, PlayerName};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Entry {
+        name: PlayerName,
+        color: PlayerColor,
+        score: GameScore,
+    }
+
+    pub fn serialize<S: serde::Serializer>(
+        board: &GameScoareboard,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let lines = board.lines().collect::<Vec<_>>();
+        lines
+            .chunks_exact(3)
+            .map(|entry| Entry {
+                name: entry[0].to_string(),
+                color: entry[1].to_string(),
+                score: entry[2].parse().unwrap_or(0),
+            })
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<GameScoareboard, D::Error> {
+        Ok(Vec::<Entry>::deserialize(deserializer)?
+            .into_iter()
+            .map(|entry| format!("{}\n{}\n{}\n", entry.name, entry.color, entry.score))
+            .collect())
+    }
 }
 
 impl std::fmt::Display for ServerPacket {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::PacketHELLO(bomb_count) => {
-                write!(f, "hello\n{}", bomb_count)
+            Self::PacketHELLO(bomb_count, version, capabilities) => {
+                write!(f, "hello\n{}\n", bomb_count)?;
+                write_protocol_support(f, &(*version, capabilities.clone()))
             }
-            Self::PacketNAME(player_name, player_color) => {
-                write!(f, "name\n{}\n{}", player_name, player_color)
+            Self::PacketNAME(player_name, player_color, resume_token) => {
+                write!(f, "name\n{}\n{}", player_name, player_color)?;
+                if let Some(resume_token) = resume_token {
+                    write!(f, "\n{}", resume_token)?;
+                }
+                Ok(())
             }
             Self::PacketSTATUS(bomb_index, bomb_position) => {
                 write!(f, "status\n{} {}", bomb_index, bomb_position)
             }
             Self::PacketBOARD(game_scoreboard) => write!(f, "board\n{}", game_scoreboard),
+            Self::PacketLOBBY(min_players, players) => {
+                write!(f, "lobby\n{}\n", min_players)?;
+                for (player_name, player_color) in players {
+                    write!(f, "{}\n{}\n", player_name, player_color)?;
+                }
+                Ok(())
+            }
+            Self::PacketBEGIN => write!(f, "begin"),
+            Self::PacketEND(cooldown, ranking) => write!(f, "end\n{}\n{}", cooldown, ranking),
+            Self::PacketERROR(code, reason) => write!(f, "error\n{}\n{}", *code as u32, reason),
+            Self::PacketSHUTDOWN(game_scoreboard) => write!(f, "shutdown\n{}", game_scoreboard),
+            Self::PacketBOMBS(bomb_count) => write!(f, "bombs\n{}", bomb_count),
+            Self::PacketSTATS(players_stats) => {
+                write!(f, "stats")?;
+                // reaction times a player doesn't have yet are written as "-"
+                let optional =
+                    |time: Option<u32>| time.map_or("-".to_string(), |time| time.to_string());
+                for (player_name, stats) in players_stats {
+                    write!(
+                        f,
+                        "\n{} {} {} {} {} {} {} {}",
+                        player_name,
+                        stats.passes,
+                        stats.timeouts,
+                        stats.bombs_received,
+                        optional(stats.best_reaction_ms),
+                        optional(stats.mean_reaction_ms),
+                        optional(stats.median_reaction_ms),
+                        stats.longest_hold_ms
+                    )?;
+                }
+                Ok(())
+            }
+            Self::PacketVIEW(phase, holders, ring) => {
+                write!(f, "view\n{}\n{}\n{}", phase, holders.join(" "), ring)
+            }
+        }
+    }
+}
+
+// Used by clients, the server only ever renders these
+impl std::str::FromStr for ServerPacket {
+    type Err = PacketError;
+
+    fn from_str(input: &str) -> Result<ServerPacket, Self::Err> {
+        let split: Vec<&str> = input.split("\n").collect();
+        // everything after the first line, scoreboards are passed on as they are
+        let body = input.split_once("\n").map(|(_, body)| body).unwrap_or("");
+        let parse_number = |text: &str| text.parse::<u32>().map_err(|_| PacketError::BadNumber);
+
+        match split[0] {
+            "hello" => {
+                if split.len() != 2 && split.len() != 3 {
+                    return Err(PacketError::LineCount);
+                }
+
+                let bomb_count = parse_number(split[1])?;
+                let (version, capabilities) = match split.get(2) {
+                    Some(line) => parse_protocol_support(line)?,
+                    None => (1, Vec::new()),
+                };
+                Ok(ServerPacket::PacketHELLO(bomb_count, version, capabilities))
+            }
+            "name" => {
+                if split.len() != 3 && split.len() != 4 {
+                    return Err(PacketError::LineCount);
+                }
+
+                Ok(ServerPacket::PacketNAME(
+                    split[1].to_string(),
+                    split[2].to_string(),
+                    split.get(3).map(|token| token.to_string()),
+                ))
+            }
+            "status" => {
+                if split.len() != 2 {
+                    return Err(PacketError::LineCount);
+                }
+
+                let (bomb_index, bomb_position) = match split[1].split_once(" ") {
+                    Some(info) => info,
+                    None => return Err(PacketError::WhitespaceCount),
+                };
+                Ok(ServerPacket::PacketSTATUS(
+                    parse_number(bomb_index)?,
+                    bomb_position.parse()?,
+                ))
+            }
+            "board" => Ok(ServerPacket::PacketBOARD(body.to_string())),
+            "lobby" => {
+                if split.len() < 2 {
+                    return Err(PacketError::LineCount);
+                }
+
+                let min_players = parse_number(split[1])?;
+                let players = split[2..]
+                    .chunks_exact(2)
+                    .map(|player| (player[0].to_string(), player[1].to_string()))
+                    .collect();
+                Ok(ServerPacket::PacketLOBBY(min_players, players))
+            }
+            "begin" => {
+                if split.len() != 1 {
+                    return Err(PacketError::LineCount);
+                }
+
+                Ok(ServerPacket::PacketBEGIN)
+            }
+            "end" => {
+                let (cooldown, ranking) = match body.split_once("\n") {
+                    Some(end) => end,
+                    None => return Err(PacketError::LineCount),
+                };
+                Ok(ServerPacket::PacketEND(
+                    parse_number(cooldown)?,
+                    ranking.to_string(),
+                ))
+            }
+            "error" => {
+                if split.len() != 3 {
+                    return Err(PacketError::LineCount);
+                }
+
+                let code = match ErrorCode::from_code(parse_number(split[1])?) {
+                    Some(code) => code,
+                    None => return Err(PacketError::BadNumber),
+                };
+                Ok(ServerPacket::PacketERROR(code, split[2].to_string()))
+            }
+            "shutdown" => Ok(ServerPacket::PacketSHUTDOWN(body.to_string())),
+            "bombs" => {
+                if split.len() != 2 {
+                    return Err(PacketError::LineCount);
+                }
+
+                Ok(ServerPacket::PacketBOMBS(parse_number(split[1])?))
+            }
+            "stats" => {
+                let parse_optional = |text: &str| match text {
+                    "-" => Ok(None),
+                    _ => parse_number(text).map(Some),
+                };
+                let mut players_stats = Vec::new();
+                for line in &split[1..] {
+                    let fields: Vec<&str> = line.split(" ").collect();
+                    if fields.len() != 8 {
+                        return Err(PacketError::WhitespaceCount);
+                    }
+                    players_stats.push((
+                        fields[0].to_string(),
+                        PlayerStats {
+                            passes: parse_number(fields[1])?,
+                            timeouts: parse_number(fields[2])?,
+                            bombs_received: parse_number(fields[3])?,
+                            best_reaction_ms: parse_optional(fields[4])?,
+                            mean_reaction_ms: parse_optional(fields[5])?,
+                            median_reaction_ms: parse_optional(fields[6])?,
+                            longest_hold_ms: parse_number(fields[7])?,
+                        },
+                    ));
+                }
+                Ok(ServerPacket::PacketSTATS(players_stats))
+            }
+            "view" => {
+                let view: Vec<&str> = input.splitn(4, "\n").collect();
+                if view.len() != 4 {
+                    return Err(PacketError::LineCount);
+                }
+
+                let holders = view[2]
+                    .split(" ")
+                    .filter(|holder| !holder.is_empty())
+                    .map(|holder| holder.to_string())
+                    .collect();
+                Ok(ServerPacket::PacketVIEW(
+                    view[1].parse()?,
+                    holders,
+                    view[3].to_string(),
+                ))
+            }
+            _ => Err(PacketError::UnknownType),
         }
     }
 }
@@ -172,3 +798,450 @@ impl From<ServerPacket> for axum::extract::ws::Message {
         axum::extract::ws::Message::Text(packet.to_string())
     }
 }
+
+pub const TEXT_PROTOCOL: &str = "bomb.text.v1";
+pub const JSON_PROTOCOL: &str = "bomb.json.v1";
+
+// How packets are put into websocket messages, picked with the Sec-WebSocket-Protocol header.
+// Clients that don't ask for a subprotocol get the text format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl WireFormat {
+    // subprotocols the server accepts, the first one the client also offers is used
+    pub const PROTOCOLS: [&'static str; 2] = [TEXT_PROTOCOL, JSON_PROTOCOL];
+
+    pub fn from_protocol(protocol: &str) -> Option<WireFormat> {
+        match protocol {
+            TEXT_PROTOCOL => Some(WireFormat::Text),
+            JSON_PROTOCOL => Some(WireFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn protocol(&self) -> &'static str {
+        match self {
+            WireFormat::Text => TEXT_PROTOCOL,
+            WireFormat::Json => JSON_PROTOCOL,
+        }
+    }
+
+    pub fn encode(&self, packet: &ServerPacket) -> axum::extract::ws::Message {
+        axum::extract::ws::Message::Text(match self {
+            WireFormat::Text => packet.to_string(),
+            WireFormat::Json => serde_json::to_string(packet).unwrap(),
+        })
+    }
+
+    // the client side of encode/decode
+    pub fn encode_client(&self, packet: &ClientPacket) -> String {
+        match self {
+            WireFormat::Text => packet.to_string(),
+            WireFormat::Json => serde_json::to_string(packet).unwrap(),
+        }
+    }
+
+    pub fn decode_server(&self, message: &str) -> Result<ServerPacket, PacketError> {
+        match self {
+            WireFormat::Text => message.parse(),
+            WireFormat::Json => serde_json::from_str(message).map_err(|_| PacketError::BadJson),
+        }
+    }
+
+    pub fn decode(&self, message: &str) -> Result<ClientPacket, PacketError> {
+        match self {
+            WireFormat::Text => message.parse(),
+            WireFormat::Json => {
+                let packet = serde_json::from_str(message).map_err(|_| PacketError::BadJson)?;
+                // tokens are checked by the text parser, JSON gets the same check
+                if let ClientPacket::PacketRESUME(resume_token, _) = &packet {
+                    if !valid_resume_token(resume_token) {
+                        return Err(PacketError::BadResumeToken);
+                    }
+                }
+                Ok(packet)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "0123456789ABCDEF0123456789abcdef";
+
+    fn protocol() -> Option<ProtocolSupport> {
+        Some((2, vec!["lobby".to_string(), "stats".to_string()]))
+    }
+
+    fn client_packets() -> Vec<(&'static str, ClientPacket)> {
+        vec![
+            ("olleh\n3", ClientPacket::PacketOLLEH(3, None, None)),
+            (
+                "olleh\n3\nAlice\n#00FF00",
+                ClientPacket::PacketOLLEH(
+                    3,
+                    None,
+                    Some(("Alice".to_string(), "#00FF00".to_string())),
+                ),
+            ),
+            (
+                "olleh\n3\n2 lobby stats",
+                ClientPacket::PacketOLLEH(3, protocol(), None),
+            ),
+            (
+                "olleh\n3\n2 lobby stats\nAlice\n#00FF00",
+                ClientPacket::PacketOLLEH(
+                    3,
+                    protocol(),
+                    Some(("Alice".to_string(), "#00FF00".to_string())),
+                ),
+            ),
+            (
+                "move\n1 L3",
+                ClientPacket::PacketMOVE(1, BombMoveAction::L3),
+            ),
+            (
+                "move\n0 R2",
+                ClientPacket::PacketMOVE(0, BombMoveAction::R2),
+            ),
+            ("start", ClientPacket::PacketSTART),
+            ("stats", ClientPacket::PacketSTATS),
+            (
+                "resume\n0123456789ABCDEF0123456789abcdef",
+                ClientPacket::PacketRESUME(TOKEN.to_string(), None),
+            ),
+            (
+                "resume\n0123456789ABCDEF0123456789abcdef\n2 lobby stats",
+                ClientPacket::PacketRESUME(TOKEN.to_string(), protocol()),
+            ),
+        ]
+    }
+
+    fn server_packets() -> Vec<(&'static str, ServerPacket)> {
+        let board = "Alice\n#00FF00\n1200\nBob\n#0000FF\n0\n".to_string();
+        vec![
+            (
+                "hello\n2\n2 lobby stats",
+                ServerPacket::PacketHELLO(2, 2, protocol().unwrap().1),
+            ),
+            (
+                "name\nAlice\n#00FF00",
+                ServerPacket::PacketNAME("Alice".to_string(), "#00FF00".to_string(), None),
+            ),
+            (
+                "name\nAlice\n#00FF00\n0123456789ABCDEF0123456789abcdef",
+                ServerPacket::PacketNAME(
+                    "Alice".to_string(),
+                    "#00FF00".to_string(),
+                    Some(TOKEN.to_string()),
+                ),
+            ),
+            (
+                "status\n1 X",
+                ServerPacket::PacketSTATUS(1, BombPosition::X),
+            ),
+            (
+                "board\nAlice\n#00FF00\n1200\nBob\n#0000FF\n0\n",
+                ServerPacket::PacketBOARD(board.clone()),
+            ),
+            ("board\n", ServerPacket::PacketBOARD(String::new())),
+            (
+                "lobby\n3\nAlice\n#00FF00\n",
+                ServerPacket::PacketLOBBY(3, vec![("Alice".to_string(), "#00FF00".to_string())]),
+            ),
+            ("begin", ServerPacket::PacketBEGIN),
+            (
+                "end\n5000\nAlice\n#00FF00\n1200\nBob\n#0000FF\n0\n",
+                ServerPacket::PacketEND(5000, board.clone()),
+            ),
+            (
+                "error\n5\nNot holding bomb 1",
+                ServerPacket::PacketERROR(ErrorCode::BombNotHeld, "Not holding bomb 1".to_string()),
+            ),
+            (
+                "shutdown\nAlice\n#00FF00\n1200\nBob\n#0000FF\n0\n",
+                ServerPacket::PacketSHUTDOWN(board.clone()),
+            ),
+            ("bombs\n4", ServerPacket::PacketBOMBS(4)),
+            (
+                "stats\nAlice 3 1 4 120 300 250 2000\nBob 0 0 0 - - - 0",
+                ServerPacket::PacketSTATS(vec![
+                    (
+                        "Alice".to_string(),
+                        PlayerStats {
+                            passes: 3,
+                            timeouts: 1,
+                            bombs_received: 4,
+                            best_reaction_ms: Some(120),
+                            mean_reaction_ms: Some(300),
+                            median_reaction_ms: Some(250),
+                            longest_hold_ms: 2000,
+                        },
+                    ),
+                    ("Bob".to_string(), PlayerStats::default()),
+                ]),
+            ),
+            (
+                "view\nplaying\nAlice Bob\nAlice\n#00FF00\n1200\nBob\n#0000FF\n0\n",
+                ServerPacket::PacketVIEW(
+                    GamePhase::Playing,
+                    vec!["Alice".to_string(), "Bob".to_string()],
+                    board.clone(),
+                ),
+            ),
+            (
+                "view\nlobby\n\n",
+                ServerPacket::PacketVIEW(GamePhase::Lobby, Vec::new(), String::new()),
+            ),
+        ]
+    }
+
+    #[test]
+    fn client_packets_round_trip_as_text() {
+        for (text, packet) in client_packets() {
+            assert_eq!(text.parse::<ClientPacket>(), Ok(packet.clone()), "{text:?}");
+            assert_eq!(packet.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn server_packets_round_trip_as_text() {
+        for (text, packet) in server_packets() {
+            assert_eq!(text.parse::<ServerPacket>(), Ok(packet.clone()), "{text:?}");
+            assert_eq!(packet.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn bad_client_packets_are_rejected() {
+        for (text, err) in [
+            ("", PacketError::UnknownType),
+            ("jump", PacketError::UnknownType),
+            ("MOVE\n0 R1", PacketError::UnknownType),
+            ("olleh", PacketError::LineCount),
+            ("olleh\n1\n2\nAlice\n#000000\nextra", PacketError::LineCount),
+            ("olleh\nfirst", PacketError::BadNumber),
+            ("olleh\n-1", PacketError::BadNumber),
+            ("olleh\n1\nlatest", PacketError::BadNumber),
+            ("olleh\n1\n2  lobby", PacketError::WhitespaceCount),
+            ("olleh\n1\n2 lob.by", PacketError::BadCapability),
+            ("move", PacketError::LineCount),
+            ("move\n0 R1\n", PacketError::LineCount),
+            ("move\n0", PacketError::WhitespaceCount),
+            ("move\n0 R1 R2", PacketError::WhitespaceCount),
+            ("move\nx R1", PacketError::BadNumber),
+            ("move\n0 R3", PacketError::BadAction),
+            ("start\n", PacketError::LineCount),
+            ("stats\nAlice", PacketError::LineCount),
+            ("resume", PacketError::LineCount),
+            ("resume\n0123", PacketError::BadResumeToken),
+            (
+                "resume\n0123456789ABCDEF0123456789ABCDEG",
+                PacketError::BadResumeToken,
+            ),
+            (
+                "resume\n0123456789ABCDEF0123456789abcdef\n2\n",
+                PacketError::LineCount,
+            ),
+        ] {
+            assert_eq!(
+                text.parse::<ClientPacket>().map(|_| ()),
+                Err(err),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn bad_server_packets_are_rejected() {
+        for (text, err) in [
+            ("hello", PacketError::LineCount),
+            ("hello\nmany", PacketError::BadNumber),
+            ("name\nAlice", PacketError::LineCount),
+            ("status\n0", PacketError::WhitespaceCount),
+            ("status\n0 Y", PacketError::BadPosition),
+            ("end\n5000", PacketError::LineCount),
+            ("error\n9\nUnknown", PacketError::BadNumber),
+            ("error\n1", PacketError::LineCount),
+            ("bombs", PacketError::LineCount),
+            (
+                "stats\nAlice 3 1 4 120 300 250",
+                PacketError::WhitespaceCount,
+            ),
+            (
+                "stats\nAlice 3 1 4 fast 300 250 2000",
+                PacketError::BadNumber,
+            ),
+            ("view\nplaying\nAlice", PacketError::LineCount),
+            ("view\nover\n\n", PacketError::BadPhase),
+            ("goodbye", PacketError::UnknownType),
+        ] {
+            assert_eq!(
+                text.parse::<ServerPacket>().map(|_| ()),
+                Err(err),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn error_codes_keep_their_numbers() {
+        let codes = [
+            (1, ErrorCode::BadPacket, 1002),
+            (2, ErrorCode::UnexpectedPacket, 1002),
+            (3, ErrorCode::NotText, 1003),
+            (4, ErrorCode::BadBombIndex, 1002),
+            (5, ErrorCode::BombNotHeld, 1008),
+            (6, ErrorCode::OllehTimeout, 1008),
+            (7, ErrorCode::ResumeRejected, 1008),
+            (8, ErrorCode::UnsupportedVersion, 1002),
+        ];
+        for (number, code, close_code) in codes {
+            assert_eq!(ErrorCode::from_code(number), Some(code));
+            assert_eq!(code as u32, number);
+            assert_eq!(code.close_code(), close_code);
+            assert_eq!(code.is_fatal(), code != ErrorCode::BombNotHeld);
+        }
+        assert_eq!(ErrorCode::from_code(0), None);
+        assert_eq!(ErrorCode::from_code(9), None);
+    }
+
+    #[test]
+    fn version_1_clients_get_no_capabilities() {
+        assert_eq!(
+            Negotiated::negotiate(None),
+            Ok(Negotiated {
+                version: 1,
+                capabilities: Vec::new(),
+            })
+        );
+        let offer = (1, vec!["lobby".to_string()]);
+        assert_eq!(
+            Negotiated::negotiate(Some(&offer)).unwrap().capabilities,
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn only_capabilities_both_sides_know_are_negotiated() {
+        let offer = (
+            2,
+            vec![
+                "stats".to_string(),
+                "teleport".to_string(),
+                "lobby".to_string(),
+            ],
+        );
+        let negotiated = Negotiated::negotiate(Some(&offer)).unwrap();
+        // in the server's order
+        assert_eq!(negotiated.capabilities, ["lobby", "stats"]);
+        assert!(negotiated.supports(CAP_STATS));
+        assert!(!negotiated.supports(CAP_BOMBS));
+        assert!(!negotiated.supports("teleport"));
+    }
+
+    #[test]
+    fn versions_outside_the_supported_range_are_refused() {
+        assert!(Negotiated::negotiate(Some(&(0, Vec::new()))).is_err());
+        assert!(Negotiated::negotiate(Some(&(PROTOCOL_VERSION + 1, Vec::new()))).is_err());
+        assert_eq!(
+            Negotiated::negotiate(Some(&(PROTOCOL_VERSION, Vec::new())))
+                .unwrap()
+                .version,
+            PROTOCOL_VERSION
+        );
+    }
+
+    #[test]
+    fn json_packets_carry_the_same_packets_as_text() {
+        let json = WireFormat::Json;
+        for (text, packet) in client_packets() {
+            let encoded = json.encode_client(&text.parse().unwrap());
+            assert_eq!(json.decode(&encoded), Ok(packet), "{encoded}");
+        }
+        for (text, packet) in server_packets() {
+            let axum::extract::ws::Message::Text(encoded) = json.encode(&text.parse().unwrap())
+            else {
+                unreachable!()
+            };
+            assert_eq!(json.decode_server(&encoded), Ok(packet), "{encoded}");
+        }
+    }
+
+    #[test]
+    fn json_packets_are_a_type_and_an_array_of_fields() {
+        assert_eq!(
+            WireFormat::Json.encode_client(&ClientPacket::PacketMOVE(1, BombMoveAction::L1)),
+            r#"{"type":"move","data":[1,"L1"]}"#
+        );
+        assert_eq!(
+            WireFormat::Json.encode_client(&ClientPacket::PacketSTART),
+            r#"{"type":"start"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ServerPacket::PacketBOARD(
+                "Alice\n#00FF00\n1200\n".to_string()
+            ))
+            .unwrap(),
+            r##"{"type":"board","data":[{"name":"Alice","color":"#00FF00","score":1200}]}"##
+        );
+    }
+
+    #[test]
+    fn json_error_codes_are_snake_case() {
+        let codes = [
+            (ErrorCode::BadPacket, "bad_packet"),
+            (ErrorCode::UnexpectedPacket, "unexpected_packet"),
+            (ErrorCode::NotText, "not_text"),
+            (ErrorCode::BadBombIndex, "bad_bomb_index"),
+            (ErrorCode::BombNotHeld, "bomb_not_held"),
+            (ErrorCode::OllehTimeout, "olleh_timeout"),
+            (ErrorCode::ResumeRejected, "resume_rejected"),
+            (ErrorCode::UnsupportedVersion, "unsupported_version"),
+        ];
+        for (code, name) in codes {
+            assert_eq!(serde_json::to_string(&code).unwrap(), format!("\"{name}\""));
+        }
+        assert_eq!(
+            serde_json::to_string(&ServerPacket::PacketERROR(
+                ErrorCode::BombNotHeld,
+                "Not holding bomb 1".to_string()
+            ))
+            .unwrap(),
+            r#"{"type":"error","data":["bomb_not_held","Not holding bomb 1"]}"#
+        );
+    }
+
+    #[test]
+    fn bad_json_packets_are_rejected() {
+        let json = WireFormat::Json;
+        assert_eq!(json.decode("move\n0 R1"), Err(PacketError::BadJson));
+        assert_eq!(json.decode(r#"{"type":"jump"}"#), Err(PacketError::BadJson));
+        assert_eq!(
+            json.decode(r#"{"type":"move","data":[0]}"#),
+            Err(PacketError::BadJson)
+        );
+        assert_eq!(
+            json.decode(r#"{"type":"resume","data":["0123",null]}"#),
+            Err(PacketError::BadResumeToken)
+        );
+        assert_eq!(
+            json.decode_server(r#"{"type":"bombs"}"#),
+            Err(PacketError::BadJson)
+        );
+    }
+
+    #[test]
+    fn wire_formats_are_picked_by_subprotocol() {
+        for format in [WireFormat::Text, WireFormat::Json] {
+            assert_eq!(WireFormat::from_protocol(format.protocol()), Some(format));
+        }
+        assert_eq!(WireFormat::from_protocol("bomb.xml.v1"), None);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::packet::{BombMoveAction, BombPosition, PlayerID, PreferredID};
+
+#[derive(Debug, Clone)]
+struct Seat {
+    player_id: PlayerID,
+    // the position the player asked for with OLLEH
+    preferred: PreferredID,
+    // away players keep their seat but bombs skip them
+    present: bool,
+}
+
+// The order players sit around the table, separate from their IDs.
+// A new player sits right after everyone who asked for the same or a smaller position,
+// so asking for the position of A seats you between A and whoever sits to the right of A.
+#[derive(Debug, Clone, Default)]
+pub struct SeatRing {
+    seats: Vec<Seat>,
+}
+
+impl SeatRing {
+    pub fn new() -> SeatRing {
+        SeatRing::default()
+    }
+
+    pub fn join(&mut self, player_id: PlayerID, preferred: PreferredID) {
+        let index = self
+            .seats
+            .iter()
+            .rposition(|seat| seat.preferred <= preferred)
+            .map_or(0, |index| index + 1);
+        self.seats.insert(
+            index,
+            Seat {
+                player_id,
+                preferred,
+                present: true,
+            },
+        );
+    }
+
+    // players whose connection dropped are away until they resume or their seat is given up
+    pub fn set_present(&mut self, player_id: PlayerID, present: bool) {
+        if let Some(seat) = self
+            .seats
+            .iter_mut()
+            .find(|seat| seat.player_id == player_id)
+        {
+            seat.present = present;
+        }
+    }
+
+    // gives up the seats of away players that keep returns false for
+    pub fn retain_away(&mut self, mut keep: impl FnMut(PlayerID) -> bool) {
+        self.seats
+            .retain(|seat| seat.present || keep(seat.player_id));
+    }
+
+    // whether the player is present, away players don't count
+    pub fn contains(&self, player_id: &PlayerID) -> bool {
+        self.seats
+            .iter()
+            .any(|seat| seat.player_id == *player_id && seat.present)
+    }
+
+    pub fn len(&self) -> usize {
+        self.seats.iter().filter(|seat| seat.present).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // present players in seat order
+    pub fn iter(&self) -> impl Iterator<Item = PlayerID> + '_ {
+        self.seats
+            .iter()
+            .filter(|seat| seat.present)
+            .map(|seat| seat.player_id)
+    }
+
+    // every player with a seat in seat order, away or not
+    pub fn seated(&self) -> impl Iterator<Item = PlayerID> + '_ {
+        self.seats.iter().map(|seat| seat.player_id)
+    }
+
+    fn seat_of(&self, player_id: PlayerID) -> Option<usize> {
+        self.seats
+            .iter()
+            .position(|seat| seat.player_id == player_id)
+    }
+
+    // the next present seat to the right (or left) of the seat at index
+    fn step(&self, index: usize, right: bool) -> usize {
+        let len = self.seats.len();
+        (1..=len)
+            .map(|offset| {
+                if right {
+                    (index + offset) % len
+                } else {
+                    (index + len - offset) % len
+                }
+            })
+            .find(|index| self.seats[*index].present)
+            .expect("nobody is present in the ring")
+    }
+
+    // who gets a bomb the holder passed on, skipping away players.
+    // the holder can be away too (e.g. their bombs are handed on when they drop),
+    // a holder without a seat is treated as sitting between the last and the first seat.
+    // panics if nobody is present
+    pub fn move_bomb(&self, holder: PlayerID, action: BombMoveAction) -> PlayerID {
+        let (steps, right) = match action {
+            BombMoveAction::L3 => (3, false),
+            BombMoveAction::L1 => (1, false),
+            BombMoveAction::R1 => (1, true),
+            BombMoveAction::R2 => (2, true),
+        };
+        let mut index = match self.seat_of(holder) {
+            Some(index) => index,
+            None if right => self.seats.len().saturating_sub(1),
+            None => 0,
+        };
+        for _ in 0..steps {
+            index = self.step(index, right);
+        }
+        self.seats[index].player_id
+    }
+
+    // where the bomb is from the view of the player
+    pub fn relative_position(&self, holder: PlayerID, player: PlayerID) -> BombPosition {
+        match (self.seat_of(holder), self.seat_of(player)) {
+            _ if holder == player => BombPosition::X,
+            (Some(holder), Some(player)) if holder > player => BombPosition::R,
+            _ => BombPosition::L,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::config::GameConfig;
+use crate::packet::GameScore;
+
+// Decides how many points a player gains or loses for what they did with a bomb.
+// Positive numbers are rewards and negative numbers are penalties,
+// a score never drops below zero.
+pub trait ScoringPolicy: Send + Sync {
+    // the player passed a bomb on after holding it for `held`
+    fn on_pass(&self, held: std::time::Duration) -> i64;
+    // the player held a bomb for `held` and it got taken away for being AFK
+    fn on_timeout(&self, held: std::time::Duration) -> i64;
+    // extra points for a pass that is the `streak`th pass in a row without a timeout
+    fn streak_bonus(&self, streak: u32) -> i64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScoringKind {
+    // score_base - milliseconds held when passing within the window, a flat penalty on timeout
+    #[default]
+    Classic,
+    // points halve every decay_half_life_ms, streaks are rewarded
+    ExponentialDecay,
+    // every pass is worth a fixed reward but holding the bomb costs points per second
+    HotPotato,
+}
+
+impl std::fmt::Display for ScoringKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ScoringKind::Classic => "classic",
+                ScoringKind::ExponentialDecay => "exponential-decay",
+                ScoringKind::HotPotato => "hot-potato",
+            }
+        )
+    }
+}
+
+// streaks stop growing the bonus after this many passes
+const MAX_STREAK_STEPS: u32 = 10;
+
+fn streak_steps(streak: u32) -> i64 {
+    streak.saturating_sub(1).min(MAX_STREAK_STEPS) as i64
+}
+
+// The original rule of the game
+#[derive(Debug, Clone)]
+pub struct ClassicScoring {
+    pub score_window_ms: u32,
+    pub score_base: u32,
+    pub timeout_penalty: u32,
+}
+
+impl ScoringPolicy for ClassicScoring {
+    fn on_pass(&self, held: std::time::Duration) -> i64 {
+        let held_ms = held.as_millis();
+        if (self.score_window_ms as u128) > held_ms {
+            (self.score_base as i64 - held_ms as i64).max(0)
+        } else {
+            0
+        }
+    }
+
+    fn on_timeout(&self, _held: std::time::Duration) -> i64 {
+        -(self.timeout_penalty as i64)
+    }
+
+    fn streak_bonus(&self, _streak: u32) -> i64 {
+        0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExponentialDecayScoring {
+    pub score_base: u32,
+    pub half_life_ms: u32,
+    pub timeout_penalty: u32,
+    pub streak_bonus: u32,
+}
+
+impl ScoringPolicy for ExponentialDecayScoring {
+    fn on_pass(&self, held: std::time::Duration) -> i64 {
+        let half_lives = held.as_millis() as f64 / self.half_life_ms.max(1) as f64;
+        (self.score_base as f64 * 0.5f64.powf(half_lives)).round() as i64
+    }
+
+    fn on_timeout(&self, _held: std::time::Duration) -> i64 {
+        -(self.timeout_penalty as i64)
+    }
+
+    fn streak_bonus(&self, streak: u32) -> i64 {
+        self.streak_bonus as i64 * streak_steps(streak)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HotPotatoScoring {
+    pub pass_reward: u32,
+    pub hold_cost_per_sec: u32,
+    pub timeout_penalty: u32,
+    pub streak_bonus: u32,
+}
+
+impl HotPotatoScoring {
+    fn hold_cost(&self, held: std::time::Duration) -> i64 {
+        (held.as_millis() as i64 * self.hold_cost_per_sec as i64) / 1000
+    }
+}
+
+impl ScoringPolicy for HotPotatoScoring {
+    fn on_pass(&self, held: std::time::Duration) -> i64 {
+        self.pass_reward as i64 - self.hold_cost(held)
+    }
+
+    fn on_timeout(&self, held: std::time::Duration) -> i64 {
+        -self.hold_cost(held) - self.timeout_penalty as i64
+    }
+
+    fn streak_bonus(&self, streak: u32) -> i64 {
+        self.streak_bonus as i64 * streak_steps(streak)
+    }
+}
+
+pub fn scoring_policy(kind: ScoringKind, config: &GameConfig) -> Box<dyn ScoringPolicy> {
+    match kind {
+        ScoringKind::Classic => Box::new(ClassicScoring {
+            score_window_ms: config.score_window_ms,
+            score_base: config.score_base,
+            timeout_penalty: config.timeout_penalty,
+        }),
+        ScoringKind::ExponentialDecay => Box::new(ExponentialDecayScoring {
+            score_base: config.score_base,
+            half_life_ms: config.decay_half_life_ms,
+            timeout_penalty: config.timeout_penalty,
+            streak_bonus: config.streak_bonus,
+        }),
+        ScoringKind::HotPotato => Box::new(HotPotatoScoring {
+            pass_reward: config.score_base,
+            hold_cost_per_sec: config.hold_cost_per_sec,
+            timeout_penalty: config.timeout_penalty,
+            streak_bonus: config.streak_bonus,
+        }),
+    }
+}
+
+pub fn apply_score_change(score: GameScore, change: i64) -> GameScore {
+    (score as i64 + change).clamp(0, GameScore::MAX as i64) as GameScore
+}
@@ -0,0 +1,1758 @@
+use axum::extract::ws;
+use axum::extract::{Path, Query, State};
+use axum::response::IntoResponse;
+
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::bot::{BotBehavior, BotDifficulty};
+use crate::clock::Clock;
+use crate::config::{GameConfig, MAX_BOMB_COUNT};
+use crate::events::EventLog;
+use crate::game::{Effect, GameState, HandoffID, PlayerUpdate, RoomInfo};
+use crate::leaderboard::{leaderboard_day, Leaderboard, LeaderboardEntry};
+use crate::packet::*;
+use crate::scoring::{scoring_policy, ScoringKind, ScoringPolicy};
+
+#[derive(Debug)]
+enum GameUpdate {
+    Player(PlayerUpdate),
+    // the player is expected to send back a BombMoveAction as response
+    BombReceived(
+        BombIndex,
+        tokio::sync::oneshot::Sender<Result<BombMoveAction, ()>>,
+    ),
+}
+
+// Sent by the player tasks to the game
+#[derive(Debug)]
+enum PlayerEvent {
+    Leave,
+    // the connection dropped without the player leaving, the seat is kept for them to resume
+    Disconnect,
+    // only the host can start the game
+    Start,
+    // the game answers with PlayerUpdate::Stats
+    Stats,
+}
+
+// Sent by the admin API to the game of a room
+#[derive(Debug)]
+enum AdminCommand {
+    Inspect(tokio::sync::oneshot::Sender<RoomInfo>),
+    // answers whether the player was in the game
+    Kick(PlayerID, tokio::sync::oneshot::Sender<bool>),
+    ResetScores,
+    // bombs aren't handed out and the round timer stops while the game is paused
+    Pause,
+    Resume,
+    // also the bomb count the following rounds start with
+    SetBombCount(BombCount),
+}
+
+// How a client answered HELLO
+#[derive(Debug)]
+// both end with how many bombs the client knows about, None if it's told about every bomb
+enum JoinRequest {
+    // Newly connected client can suggest a position/ID for the player, and a name and color
+    Olleh(PreferredID, Option<PlayerData>, Option<BombCount>),
+    // the client takes back the seat of a player whose connection dropped
+    Resume(ResumeToken, Option<BombCount>),
+}
+
+// Player data are only created after olleh packet
+type JoinResult = (
+    PlayerID,
+    PlayerData,
+    ResumeToken,
+    // the bomb count can change between HELLO and joining
+    BombCount,
+    tokio::sync::mpsc::Receiver<GameUpdate>,
+    tokio::sync::watch::Receiver<GameScoareboard>,
+    tokio::sync::mpsc::Sender<(PlayerID, PlayerEvent)>,
+);
+
+// the game drops the result sender if it can't take the request (e.g. a bad resume token)
+type OllehSender =
+    tokio::sync::oneshot::Sender<(JoinRequest, tokio::sync::oneshot::Sender<JoinResult>)>;
+
+// Channel for a newly created websocket handler to ask for a game to join
+type GameRequest = tokio::sync::oneshot::Sender<(
+    // bomb count is sent before hello packet
+    BombCount,
+    OllehSender,
+)>;
+
+type RoomName = String;
+
+// the scores players that aren't bots reached in a round, or before leaving in the middle of one
+type RoundResults = Vec<(PlayerName, GameScore)>;
+
+// what spectators see: the phase, the holder of every bomb and the ring with scores
+type SpectatorView = (GamePhase, Vec<PlayerName>, GameScoareboard);
+
+struct Room {
+    game_request_tx: tokio::sync::mpsc::Sender<GameRequest>,
+    admin_tx: tokio::sync::mpsc::Sender<AdminCommand>,
+    // the game publishes its state here for spectators, who never talk to the game itself
+    view_rx: tokio::sync::watch::Receiver<SpectatorView>,
+}
+
+const DEFAULT_ROOM: &str = "default";
+
+type LiveNames = std::sync::Arc<
+    std::sync::Mutex<std::collections::HashMap<RoomName, std::collections::BTreeSet<PlayerName>>>,
+>;
+
+// Everything the handlers share, the server runs as long as a copy of it is around
+#[derive(Clone)]
+pub struct AppState {
+    // room name -> channels of the game task running in that room
+    // a room is created when the first player joins and removed when its game task ends
+    rooms: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<RoomName, Room>>>,
+    // room name -> the names of the players in that room, a name is only used in one room at a time
+    // so nobody adds to the leaderboard entry of a player who is playing somewhere else
+    live_names: LiveNames,
+    // every game spawned in a room starts with this configuration
+    config: std::sync::Arc<GameConfig>,
+    // the admin API is disabled without a token
+    admin_token: std::sync::Arc<Option<String>>,
+    // games send their results here, the leaderboard task publishes the updated leaderboard
+    results_tx: tokio::sync::mpsc::Sender<RoundResults>,
+    leaderboard_rx: tokio::sync::watch::Receiver<Leaderboard>,
+    // every game writes its events to a file in this directory, no logs are kept without one
+    event_log_dir: std::sync::Arc<Option<std::path::PathBuf>>,
+    clock: std::sync::Arc<dyn Clock>,
+    // set to true once the server starts shutting down
+    shutdown: std::sync::Arc<tokio::sync::watch::Sender<bool>>,
+    // every task holding a copy of the state keeps this open,
+    // main waits for the channel to close before exiting
+    _running: tokio::sync::mpsc::Sender<()>,
+}
+
+impl AppState {
+    pub fn new(
+        config: GameConfig,
+        admin_token: Option<String>,
+        leaderboard: Leaderboard,
+        leaderboard_path: Option<std::path::PathBuf>,
+        event_log_dir: Option<std::path::PathBuf>,
+        clock: std::sync::Arc<dyn Clock>,
+    ) -> (Self, tokio::sync::mpsc::Receiver<()>) {
+        let (running_tx, running_rx) = tokio::sync::mpsc::channel(1);
+        let (results_tx, results_rx) = tokio::sync::mpsc::channel(32);
+        let (leaderboard_tx, leaderboard_rx) = tokio::sync::watch::channel(leaderboard);
+        tokio::spawn(leaderboard_writer(
+            results_rx,
+            leaderboard_tx,
+            leaderboard_path,
+            clock.clone(),
+            running_tx.clone(),
+        ));
+        (
+            AppState {
+                rooms: std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+                live_names: LiveNames::default(),
+                config: std::sync::Arc::new(config),
+                admin_token: std::sync::Arc::new(admin_token),
+                results_tx,
+                leaderboard_rx,
+                event_log_dir: std::sync::Arc::new(event_log_dir),
+                clock,
+                shutdown: std::sync::Arc::new(tokio::sync::watch::channel(false).0),
+                _running: running_tx,
+            },
+            running_rx,
+        )
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    // asks every game to end and stops new players from joining
+    pub fn shut_down(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    // returns the channel of the game running in the room, spawning a new game if there's none
+    // the options are only used if this creates the room
+    fn join_room(
+        &self,
+        room: &RoomName,
+        options: &RoomOptions,
+    ) -> tokio::sync::mpsc::Sender<GameRequest> {
+        let mut rooms = self.rooms.lock().unwrap();
+        if let Some(current) = rooms.get(room) {
+            if !current.game_request_tx.is_closed() {
+                return current.game_request_tx.clone();
+            }
+        }
+
+        let mut config = self.config.as_ref().clone();
+        options.apply(&mut config);
+        let seed = config.seed.unwrap_or_else(rand::random);
+        println!(
+            "Creating room {room} with {} scoring, {} {} bots and seed {seed}...",
+            config.scoring, config.bot_fill, config.bot_difficulty
+        );
+        let (game_request_tx, game_request_rx) = tokio::sync::mpsc::channel(32);
+        let (view_tx, view_rx) =
+            tokio::sync::watch::channel((GamePhase::Lobby, Vec::new(), String::new()));
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(8);
+        rooms.insert(
+            room.clone(),
+            Room {
+                game_request_tx: game_request_tx.clone(),
+                admin_tx,
+                view_rx,
+            },
+        );
+
+        let state = self.clone();
+        let room = room.clone();
+        let room_request_tx = game_request_tx.clone();
+        let context = GameContext {
+            room: room.clone(),
+            shutdown: self.shutdown.subscribe(),
+            view_tx,
+            admin_rx,
+            results_tx: self.results_tx.clone(),
+            live_names: self.live_names.clone(),
+            event_log_dir: self.event_log_dir.as_ref().clone(),
+            seed,
+            clock: self.clock.clone(),
+        };
+        tokio::spawn(async move {
+            let scoring = scoring_policy(config.scoring, &config);
+            game_server(game_request_rx, config, scoring, context).await;
+            println!("Room {room} closed...");
+            state.close_room(&room, &room_request_tx);
+        });
+
+        game_request_tx
+    }
+
+    // only removes the room if it's still the game the caller knows about
+    fn close_room(
+        &self,
+        room: &RoomName,
+        game_request_tx: &tokio::sync::mpsc::Sender<GameRequest>,
+    ) {
+        let mut rooms = self.rooms.lock().unwrap();
+        if let Some(current) = rooms.get(room) {
+            if current.game_request_tx.same_channel(game_request_tx) {
+                rooms.remove(room);
+                self.live_names.lock().unwrap().remove(room);
+            }
+        }
+    }
+
+    // the admin channels of every room with a running game, sorted by room name
+    fn admin_rooms(&self) -> Vec<(RoomName, tokio::sync::mpsc::Sender<AdminCommand>)> {
+        let rooms = self.rooms.lock().unwrap();
+        let mut admin_rooms = rooms
+            .iter()
+            .filter(|(_, current)| !current.game_request_tx.is_closed())
+            .map(|(room, current)| (room.clone(), current.admin_tx.clone()))
+            .collect::<Vec<_>>();
+        admin_rooms.sort_by(|(a, _), (b, _)| a.cmp(b));
+        admin_rooms
+    }
+
+    fn admin_room(&self, room: &RoomName) -> Option<tokio::sync::mpsc::Sender<AdminCommand>> {
+        let rooms = self.rooms.lock().unwrap();
+        rooms
+            .get(room)
+            .filter(|current| !current.game_request_tx.is_closed())
+            .map(|current| current.admin_tx.clone())
+    }
+
+    // spectators can only watch rooms that already have a game
+    fn spectate_room(
+        &self,
+        room: &RoomName,
+    ) -> Option<tokio::sync::watch::Receiver<SpectatorView>> {
+        let rooms = self.rooms.lock().unwrap();
+        rooms
+            .get(room)
+            .filter(|current| !current.game_request_tx.is_closed())
+            .map(|current| current.view_rx.clone())
+    }
+}
+
+// Options a client can ask for with the query string when its connection creates a room
+#[derive(Debug, Clone, Default)]
+struct RoomOptions {
+    scoring: Option<ScoringKind>,
+    lobby_min_players: Option<u32>,
+    round_duration_ms: Option<u64>,
+    round_target_score: Option<u32>,
+    bot_fill: Option<u32>,
+    bot_difficulty: Option<BotDifficulty>,
+    seed: Option<u64>,
+}
+
+// rooms can't ask for more bots than this
+const MAX_ROOM_BOTS: u32 = 16;
+
+impl RoomOptions {
+    fn from_query(
+        query: &std::collections::HashMap<String, String>,
+    ) -> Result<RoomOptions, &'static str> {
+        let mut options = RoomOptions::default();
+        if let Some(scoring) = query.get("scoring") {
+            options.scoring = match <ScoringKind as clap::ValueEnum>::from_str(scoring, true) {
+                Ok(scoring) => Some(scoring),
+                Err(_) => return Err("Unknown scoring rule"),
+            };
+        }
+        if let Some(lobby_min_players) = query.get("min_players") {
+            options.lobby_min_players = match lobby_min_players.parse() {
+                Ok(lobby_min_players) => Some(lobby_min_players),
+                Err(_) => return Err("Bad minimum player count"),
+            };
+        }
+        if let Some(round_duration_ms) = query.get("round_ms") {
+            options.round_duration_ms = match round_duration_ms.parse() {
+                Ok(round_duration_ms) => Some(round_duration_ms),
+                Err(_) => return Err("Bad round duration"),
+            };
+        }
+        if let Some(round_target_score) = query.get("target_score") {
+            options.round_target_score = match round_target_score.parse() {
+                Ok(round_target_score) => Some(round_target_score),
+                Err(_) => return Err("Bad target score"),
+            };
+        }
+        if let Some(bot_fill) = query.get("bots") {
+            options.bot_fill = match bot_fill.parse() {
+                Ok(bot_fill) if bot_fill <= MAX_ROOM_BOTS => Some(bot_fill),
+                _ => return Err("Bad bot count"),
+            };
+        }
+        if let Some(bot_difficulty) = query.get("bot_difficulty") {
+            options.bot_difficulty =
+                match <BotDifficulty as clap::ValueEnum>::from_str(bot_difficulty, true) {
+                    Ok(bot_difficulty) => Some(bot_difficulty),
+                    Err(_) => return Err("Unknown bot difficulty"),
+                };
+        }
+        if let Some(seed) = query.get("seed") {
+            options.seed = match seed.parse() {
+                Ok(seed) => Some(seed),
+                Err(_) => return Err("Bad seed"),
+            };
+        }
+        Ok(options)
+    }
+
+    fn apply(&self, config: &mut GameConfig) {
+        if let Some(scoring) = self.scoring {
+            config.scoring = scoring;
+        }
+        if let Some(lobby_min_players) = self.lobby_min_players {
+            config.lobby_min_players = lobby_min_players;
+        }
+        if let Some(round_duration_ms) = self.round_duration_ms {
+            config.round_duration_ms = round_duration_ms;
+        }
+        if let Some(round_target_score) = self.round_target_score {
+            config.round_target_score = round_target_score;
+        }
+        if let Some(bot_fill) = self.bot_fill {
+            config.bot_fill = bot_fill;
+        }
+        if let Some(bot_difficulty) = self.bot_difficulty {
+            config.bot_difficulty = bot_difficulty;
+        }
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+    }
+}
+
+fn valid_room_name(room: &str) -> bool {
+    !room.is_empty()
+        && room.len() <= 32
+        && room
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// resume tokens guard the seats of players, so they never come from the seed of the room
+fn random_resume_token() -> ResumeToken {
+    format!("{:032X}", rand::random::<u128>())
+}
+
+// every update a single change of the game can send a player fits in their channel
+const PLAYER_CHANNEL_SIZE: usize = 4 * MAX_BOMB_COUNT as usize;
+
+// what the game gets back from a joining player, marked with whether they are a bot
+type OllehResult = (
+    bool,
+    Result<
+        (JoinRequest, tokio::sync::oneshot::Sender<JoinResult>),
+        tokio::sync::oneshot::error::RecvError,
+    >,
+);
+
+// what the game gets back from a bomb holder: the handoff and their answer
+type BombActionResult = (
+    HandoffID,
+    Result<Result<BombMoveAction, ()>, tokio::sync::oneshot::error::RecvError>,
+);
+
+// only wakes up the receivers if the value is different
+fn publish<T: PartialEq>(tx: &tokio::sync::watch::Sender<T>, value: T) {
+    tx.send_if_modified(|current| {
+        if *current == value {
+            return false;
+        }
+        *current = value;
+        true
+    });
+}
+
+// Carries out the effects of a game on the channels of its players and the rest of the server
+struct GameDriver {
+    players_channel: std::collections::BTreeMap<PlayerID, tokio::sync::mpsc::Sender<GameUpdate>>,
+    // answers of the players the game handed a bomb to
+    wait_bomb_action: tokio::task::JoinSet<BombActionResult>,
+    wait_olleh: tokio::task::JoinSet<OllehResult>,
+    events: EventLog,
+    results_tx: tokio::sync::mpsc::Sender<RoundResults>,
+    bot_behavior: BotBehavior,
+    afk_timeout: tokio::time::Duration,
+}
+
+impl GameDriver {
+    async fn apply(&mut self, effects: Vec<Effect>) {
+        for effect in effects {
+            match effect {
+                Effect::Send(player_id, update) => {
+                    self.send(player_id, GameUpdate::Player(update));
+                }
+                Effect::GiveBomb(holder, bomb_index, handoff) => {
+                    // a holder that isn't there anymore drops the channel of their answer
+                    let (action_tx, action_rx) = tokio::sync::oneshot::channel();
+                    self.send(holder, GameUpdate::BombReceived(bomb_index, action_tx));
+                    self.wait_bomb_action
+                        .spawn(async move { (handoff, action_rx.await) });
+                }
+                Effect::SpawnBot(seed) => {
+                    let (wait_olleh_tx, wait_olleh_rx) = tokio::sync::oneshot::channel();
+                    self.wait_olleh
+                        .spawn(async move { (true, wait_olleh_rx.await) });
+                    tokio::spawn(bot_player(
+                        wait_olleh_tx,
+                        self.bot_behavior.clone(),
+                        self.afk_timeout,
+                        seed,
+                    ));
+                }
+                Effect::Results(results) => {
+                    let _ = self.results_tx.send(results).await;
+                }
+                Effect::Log(event) => self.events.log(event),
+                Effect::Print(line) => println!("{line}"),
+            }
+        }
+    }
+
+    // the game never waits for a player: a player whose channel is full can't keep up with it,
+    // their channel is dropped and they leave once their connection sees that
+    fn send(&mut self, player_id: PlayerID, update: GameUpdate) {
+        // the player may be gone already, the game hears about it soon
+        let Some(channel) = self.players_channel.get(&player_id) else {
+            return;
+        };
+        if let Err(tokio::sync::mpsc::error::TrySendError::Full(_)) = channel.try_send(update) {
+            println!("{player_id} can't keep up with the game, dropping them...");
+            self.players_channel.remove(&player_id);
+        }
+    }
+}
+
+async fn ws_get_handler(
+    ws: ws::WebSocketUpgrade,
+    Query(query): Query<std::collections::HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    let room = query
+        .get("room")
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_ROOM.to_string());
+    ws_upgrade(ws, room, &query, state)
+}
+
+async fn ws_room_get_handler(
+    ws: ws::WebSocketUpgrade,
+    Path(room): Path<RoomName>,
+    Query(query): Query<std::collections::HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    ws_upgrade(ws, room, &query, state)
+}
+
+fn ws_upgrade(
+    ws: ws::WebSocketUpgrade,
+    room: RoomName,
+    query: &std::collections::HashMap<String, String>,
+    state: AppState,
+) -> axum::response::Response {
+    if state.is_shutting_down() {
+        return (
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            "Server is shutting down",
+        )
+            .into_response();
+    }
+    if !valid_room_name(&room) {
+        return (axum::http::StatusCode::BAD_REQUEST, "Invalid room name").into_response();
+    }
+    let options = match RoomOptions::from_query(query) {
+        Ok(options) => options,
+        Err(reason) => return (axum::http::StatusCode::BAD_REQUEST, reason).into_response(),
+    };
+    ws.protocols(WireFormat::PROTOCOLS)
+        .on_upgrade(|socket| ws_client_handler(socket, state, room, options))
+}
+
+async fn spectate_get_handler(
+    ws: ws::WebSocketUpgrade,
+    Query(query): Query<std::collections::HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    let room = query
+        .get("room")
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_ROOM.to_string());
+    spectate_upgrade(ws, room, state)
+}
+
+async fn spectate_room_get_handler(
+    ws: ws::WebSocketUpgrade,
+    Path(room): Path<RoomName>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    spectate_upgrade(ws, room, state)
+}
+
+fn spectate_upgrade(
+    ws: ws::WebSocketUpgrade,
+    room: RoomName,
+    state: AppState,
+) -> axum::response::Response {
+    if state.is_shutting_down() {
+        return (
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            "Server is shutting down",
+        )
+            .into_response();
+    }
+    if !valid_room_name(&room) {
+        return (axum::http::StatusCode::BAD_REQUEST, "Invalid room name").into_response();
+    }
+    let view_rx = match state.spectate_room(&room) {
+        Some(view_rx) => view_rx,
+        None => {
+            return (
+                axum::http::StatusCode::NOT_FOUND,
+                "No game is running in this room",
+            )
+                .into_response()
+        }
+    };
+    ws.protocols(WireFormat::PROTOCOLS)
+        .on_upgrade(|socket| spectator_handler(socket, state, room, view_rx))
+}
+
+// the whole server, the pages and scripts of the web client are served from assets_dir
+pub fn router(state: AppState, assets_dir: &std::path::Path) -> Router {
+    Router::new()
+        .fallback_service(axum::routing::get_service(
+            tower_http::services::ServeDir::new(assets_dir).append_index_html_on_directories(true),
+        ))
+        .route("/ws", get(ws_get_handler))
+        .route("/ws/:room", get(ws_room_get_handler))
+        .route("/spectate", get(spectate_get_handler))
+        .route("/spectate/:room", get(spectate_room_get_handler))
+        .route("/api/leaderboard", get(leaderboard_get_handler))
+        .route_service(
+            "/leaderboard",
+            tower_http::services::ServeFile::new(assets_dir.join("leaderboard.html")),
+        )
+        .nest("/admin", admin_router(state.clone()))
+        .with_state(state)
+}
+
+fn admin_router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/rooms", get(admin_list_rooms))
+        .route("/rooms/:room", get(admin_inspect_room))
+        .route(
+            "/rooms/:room/players/:player_id/kick",
+            post(admin_kick_player),
+        )
+        .route("/rooms/:room/reset-scores", post(admin_reset_scores))
+        .route("/rooms/:room/pause", post(admin_pause))
+        .route("/rooms/:room/resume", post(admin_resume))
+        .route("/rooms/:room/bomb-count", post(admin_set_bomb_count))
+        .route_layer(axum::middleware::from_fn_with_state(state, require_admin))
+}
+
+// compares every byte so the time taken doesn't tell how much of the token was right
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// every admin request needs an "Authorization: Bearer <admin_token>" header
+async fn require_admin<B>(
+    State(state): State<AppState>,
+    request: axum::http::Request<B>,
+    next: axum::middleware::Next<B>,
+) -> axum::response::Response {
+    let token = match state.admin_token.as_ref() {
+        Some(token) => token,
+        None => {
+            return (axum::http::StatusCode::NOT_FOUND, "Admin API is disabled").into_response()
+        }
+    };
+    let authorized = request
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .is_some_and(|given| token_matches(given, token));
+    if !authorized {
+        return (
+            axum::http::StatusCode::UNAUTHORIZED,
+            [(axum::http::header::WWW_AUTHENTICATE, "Bearer")],
+            "Missing or wrong admin token",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+async fn inspect_room(admin_tx: &tokio::sync::mpsc::Sender<AdminCommand>) -> Option<RoomInfo> {
+    let (info_tx, info_rx) = tokio::sync::oneshot::channel();
+    admin_tx.send(AdminCommand::Inspect(info_tx)).await.ok()?;
+    info_rx.await.ok()
+}
+
+// sends a command that doesn't need an answer to the game of the room
+async fn send_admin_command(
+    state: &AppState,
+    room: &RoomName,
+    command: AdminCommand,
+) -> axum::response::Response {
+    match state.admin_room(room) {
+        Some(admin_tx) if admin_tx.send(command).await.is_ok() => {
+            axum::http::StatusCode::NO_CONTENT.into_response()
+        }
+        _ => (
+            axum::http::StatusCode::NOT_FOUND,
+            "No game is running in this room",
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct RoomListing {
+    room: RoomName,
+    #[serde(flatten)]
+    info: RoomInfo,
+}
+
+async fn admin_list_rooms(State(state): State<AppState>) -> axum::response::Response {
+    let mut listings = Vec::new();
+    for (room, admin_tx) in state.admin_rooms() {
+        // rooms that close while being asked are left out
+        if let Some(info) = inspect_room(&admin_tx).await {
+            listings.push(RoomListing { room, info });
+        }
+    }
+    axum::Json(listings).into_response()
+}
+
+async fn admin_inspect_room(
+    Path(room): Path<RoomName>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    let info = match state.admin_room(&room) {
+        Some(admin_tx) => inspect_room(&admin_tx).await,
+        None => None,
+    };
+    match info {
+        Some(info) => axum::Json(info).into_response(),
+        None => (
+            axum::http::StatusCode::NOT_FOUND,
+            "No game is running in this room",
+        )
+            .into_response(),
+    }
+}
+
+async fn admin_kick_player(
+    Path((room, player_id)): Path<(RoomName, PlayerID)>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    let admin_tx = match state.admin_room(&room) {
+        Some(admin_tx) => admin_tx,
+        None => {
+            return (
+                axum::http::StatusCode::NOT_FOUND,
+                "No game is running in this room",
+            )
+                .into_response()
+        }
+    };
+    let (kicked_tx, kicked_rx) = tokio::sync::oneshot::channel();
+    let kicked = admin_tx
+        .send(AdminCommand::Kick(player_id, kicked_tx))
+        .await
+        .is_ok()
+        && kicked_rx.await.unwrap_or(false);
+    if kicked {
+        axum::http::StatusCode::NO_CONTENT.into_response()
+    } else {
+        (
+            axum::http::StatusCode::NOT_FOUND,
+            "No such player in this room",
+        )
+            .into_response()
+    }
+}
+
+async fn admin_reset_scores(
+    Path(room): Path<RoomName>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    send_admin_command(&state, &room, AdminCommand::ResetScores).await
+}
+
+async fn admin_pause(
+    Path(room): Path<RoomName>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    send_admin_command(&state, &room, AdminCommand::Pause).await
+}
+
+async fn admin_resume(
+    Path(room): Path<RoomName>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    send_admin_command(&state, &room, AdminCommand::Resume).await
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BombCountRequest {
+    bomb_count: BombCount,
+}
+
+async fn admin_set_bomb_count(
+    Path(room): Path<RoomName>,
+    State(state): State<AppState>,
+    axum::Json(request): axum::Json<BombCountRequest>,
+) -> axum::response::Response {
+    if !(1..=MAX_BOMB_COUNT).contains(&request.bomb_count) {
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("The bomb count must be between 1 and {MAX_BOMB_COUNT}"),
+        )
+            .into_response();
+    }
+    send_admin_command(
+        &state,
+        &room,
+        AdminCommand::SetBombCount(request.bomb_count),
+    )
+    .await
+}
+
+// owns the leaderboard: adds the results the games send, saves it and publishes it
+// ends once every game and the server are gone, after saving the last results
+async fn leaderboard_writer(
+    mut results_rx: tokio::sync::mpsc::Receiver<RoundResults>,
+    leaderboard_tx: tokio::sync::watch::Sender<Leaderboard>,
+    path: Option<std::path::PathBuf>,
+    clock: std::sync::Arc<dyn Clock>,
+    _running: tokio::sync::mpsc::Sender<()>,
+) {
+    while let Some(results) = results_rx.recv().await {
+        if results.is_empty() {
+            continue;
+        }
+        let day = leaderboard_day(clock.system_time());
+        leaderboard_tx.send_modify(|leaderboard| {
+            for (name, score) in &results {
+                leaderboard.record(&day, name, *score);
+            }
+        });
+        if let Some(path) = &path {
+            let leaderboard = leaderboard_tx.borrow().clone();
+            let path = path.clone();
+            let saved = tokio::task::spawn_blocking(move || leaderboard.save(&path))
+                .await
+                .unwrap();
+            if let Err(err) = saved {
+                println!("Failed saving the leaderboard: {}", err);
+            }
+        }
+    }
+}
+
+// the leaderboard API never returns more entries than this
+const MAX_LEADERBOARD_LIMIT: usize = 100;
+
+#[derive(Debug, serde::Serialize)]
+struct LeaderboardResponse {
+    day: String,
+    all_time: Vec<LeaderboardEntry>,
+    daily: Vec<LeaderboardEntry>,
+}
+
+// ?day=YYYY-MM-DD picks the daily leaderboard (today in UTC by default), ?limit= the entry count
+async fn leaderboard_get_handler(
+    Query(query): Query<std::collections::HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> axum::response::Response {
+    let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => 20,
+        Some(Ok(limit)) if limit <= MAX_LEADERBOARD_LIMIT => limit,
+        Some(_) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                "The limit must be between 0 and 100",
+            )
+                .into_response()
+        }
+    };
+    let day = query
+        .get("day")
+        .cloned()
+        .unwrap_or_else(|| leaderboard_day(state.clock.system_time()));
+    let leaderboard = state.leaderboard_rx.borrow();
+    axum::Json(LeaderboardResponse {
+        all_time: leaderboard.all_time(limit),
+        daily: leaderboard.daily(&day, limit),
+        day,
+    })
+    .into_response()
+}
+
+// tells the client what was wrong with its packet, closing the connection if the error is fatal
+// clients without the error capability only get the close frame
+async fn send_error(
+    socket: &mut ws::WebSocket,
+    wire_format: WireFormat,
+    negotiated: &Negotiated,
+    code: ErrorCode,
+    reason: &str,
+) {
+    if negotiated.supports(CAP_ERROR) {
+        let _ = socket
+            .send(wire_format.encode(&ServerPacket::PacketERROR(code, reason.to_string())))
+            .await;
+    }
+    if code.is_fatal() {
+        let _ = socket
+            .send(ws::Message::Close(Some(ws::CloseFrame {
+                code: code.close_code(),
+                reason: reason.to_string().into(),
+            })))
+            .await;
+    }
+}
+
+// the client can come back once the server is up again
+async fn close_for_shutdown(socket: &mut ws::WebSocket) {
+    let _ = socket
+        .send(ws::Message::Close(Some(ws::CloseFrame {
+            code: ws::close_code::AWAY,
+            reason: "Server is shutting down".into(),
+        })))
+        .await;
+}
+
+async fn ws_client_handler(
+    mut socket: ws::WebSocket,
+    state: AppState,
+    room: RoomName,
+    options: RoomOptions,
+) {
+    // the text format is used when the client didn't ask for a subprotocol
+    let wire_format = socket
+        .protocol()
+        .and_then(|protocol| protocol.to_str().ok())
+        .and_then(WireFormat::from_protocol)
+        .unwrap_or_default();
+    println!(
+        "New websocket connection has established using {}...",
+        wire_format.protocol()
+    );
+
+    println!("Requesting server connection for a new player to join room {room}...");
+    let (bomb_count, olleh_tx) = loop {
+        if state.is_shutting_down() {
+            close_for_shutdown(&mut socket).await;
+            return;
+        }
+        let game_request_tx = state.join_room(&room, &options);
+        let (first_result_tx, first_result_rx) = tokio::sync::oneshot::channel();
+        if game_request_tx.send(first_result_tx).await.is_ok() {
+            if let Ok(first_result) = first_result_rx.await {
+                break first_result;
+            }
+        }
+        // the game task ended while we were joining, start over with a new one
+        println!("Room {room} closed before accepting the new player, retrying...");
+        state.close_room(&room, &game_request_tx);
+    };
+
+    if socket
+        .send(wire_format.encode(&ServerPacket::PacketHELLO(
+            bomb_count,
+            PROTOCOL_VERSION,
+            SERVER_CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        )))
+        .await
+        .is_err()
+    {
+        println!("A websocket connection abruptly closed before getting a HELLO...");
+        return;
+    }
+    // the client may get every packet until it says what it understands
+    let mut negotiated = Negotiated::latest();
+
+    let mut shutdown = state.shutdown.subscribe();
+    let response = tokio::select! {
+        response = tokio::time::timeout(
+            tokio::time::Duration::from_millis(state.config.olleh_timeout_ms),
+            socket.recv(),
+        ) => response,
+        _ = shutdown.changed() => {
+            close_for_shutdown(&mut socket).await;
+            return;
+        }
+    };
+    let response = match response {
+        Err(_) => {
+            println!("A websocket connection took too long to send a OLLEH response...");
+            send_error(
+                &mut socket,
+                wire_format,
+                &negotiated,
+                ErrorCode::OllehTimeout,
+                "No OLLEH received in time",
+            )
+            .await;
+            return;
+        }
+        Ok(None) => {
+            println!("A websocket connection abruptly closed before sending a OLLEH response...");
+            return;
+        }
+        Ok(Some(Err(_))) => {
+            println!("A websocket connection caused a error before sending a OLLEH response...");
+            let _ = socket
+                .send(axum::extract::ws::Message::Close(Option::None))
+                .await;
+            return;
+        }
+        Ok(Some(Ok(response))) => response,
+    };
+
+    let text_response = match response {
+        ws::Message::Close(_) => {
+            println!("A websocket connection closed before sending a OLLEH response...");
+            return;
+        }
+        ws::Message::Text(text_response) => text_response,
+        _ => {
+            println!("A websocket connection sent a OLLEH response that's not a text message...");
+            send_error(
+                &mut socket,
+                wire_format,
+                &negotiated,
+                ErrorCode::NotText,
+                "Only text messages are supported",
+            )
+            .await;
+            return;
+        }
+    };
+
+    let (mut join_request, protocol) = match wire_format.decode(&text_response) {
+        Err(err) => {
+            println!("A websocket connection sent a packet expected to be a OLLEH but failed parsing:\n\t{}", err);
+            send_error(
+                &mut socket,
+                wire_format,
+                &negotiated,
+                ErrorCode::BadPacket,
+                &err.to_string(),
+            )
+            .await;
+            return;
+        }
+        Ok(ClientPacket::PacketOLLEH(suggested_pos, protocol, player_data)) => (
+            JoinRequest::Olleh(suggested_pos, player_data, None),
+            protocol,
+        ),
+        Ok(ClientPacket::PacketRESUME(resume_token, protocol)) => {
+            (JoinRequest::Resume(resume_token, None), protocol)
+        }
+        Ok(packet) => {
+            println!(
+                "A websocket connection sent a packet expected to be a OLLEH but is a {:?}",
+                packet
+            );
+            send_error(
+                &mut socket,
+                wire_format,
+                &negotiated,
+                ErrorCode::UnexpectedPacket,
+                "Expected a OLLEH or RESUME packet",
+            )
+            .await;
+            return;
+        }
+    };
+
+    negotiated = match Negotiated::negotiate(protocol.as_ref()) {
+        Ok(negotiated) => negotiated,
+        Err(reason) => {
+            println!("A websocket connection asked for a protocol the server can't talk...");
+            send_error(
+                &mut socket,
+                wire_format,
+                &negotiated,
+                ErrorCode::UnsupportedVersion,
+                &reason,
+            )
+            .await;
+            return;
+        }
+    };
+    println!(
+        "The client talks protocol version {} with {:?}...",
+        negotiated.version, negotiated.capabilities
+    );
+
+    // clients without the bombs capability only see the bombs HELLO told them about,
+    // the game passes the bombs added after that on for them
+    if !negotiated.supports(CAP_BOMBS) {
+        match &mut join_request {
+            JoinRequest::Olleh(_, _, bomb_limit) | JoinRequest::Resume(_, bomb_limit) => {
+                *bomb_limit = Some(bomb_count)
+            }
+        }
+    }
+
+    println!("Requesting server connection for a new player to join...");
+    let (request_result_tx, request_result_rx) = tokio::sync::oneshot::channel();
+    if olleh_tx.send((join_request, request_result_tx)).is_err() {
+        // the game only goes away before answering when the server shuts down
+        close_for_shutdown(&mut socket).await;
+        return;
+    }
+
+    let (
+        player_id,
+        (player_name, player_color),
+        resume_token,
+        current_bomb_count,
+        mut update_receiver,
+        mut scoreboard_receiver,
+        player_event_notify,
+    ) = match request_result_rx.await {
+        Ok(join_result) => join_result,
+        Err(_) if state.is_shutting_down() => {
+            close_for_shutdown(&mut socket).await;
+            return;
+        }
+        Err(_) => {
+            println!("The game refused to let the websocket connection resume...");
+            send_error(
+                &mut socket,
+                wire_format,
+                &negotiated,
+                ErrorCode::ResumeRejected,
+                "Unknown resume token or the player is still connected",
+            )
+            .await;
+            return;
+        }
+    };
+    println!("Received server connection and player data for new player...");
+
+    // a player whose connection dropped without a close frame may come back with the resume token,
+    // a connection that can't be sent to is treated like one that dropped
+    let mut disconnected = socket
+        .send(wire_format.encode(&ServerPacket::PacketNAME(
+            player_name,
+            player_color,
+            Some(resume_token).filter(|_| negotiated.supports(CAP_RESUME)),
+        )))
+        .await
+        .is_err();
+
+    if current_bomb_count != bomb_count && negotiated.supports(CAP_BOMBS) && !disconnected {
+        disconnected = socket
+            .send(wire_format.encode(&ServerPacket::PacketBOMBS(current_bomb_count)))
+            .await
+            .is_err();
+    }
+
+    // one entry for every bomb currently in the game
+    let mut bomb_actions: Vec<Option<tokio::sync::oneshot::Sender<Result<BombMoveAction, ()>>>> =
+        Vec::new();
+    bomb_actions.resize_with(current_bomb_count as usize, || Option::None);
+
+    let mut wait_bomb_timeout = tokio::task::JoinSet::<(u32, BombIndex)>::new();
+    // never shrinks, so timers of removed bombs stay stale if the bombs come back
+    // and MOVEs for bombs that were just removed aren't mistaken for bad indices
+    let mut bomb_counter = vec![0u32; bomb_count.max(current_bomb_count) as usize];
+
+    let afk_timeout = tokio::time::Duration::from_millis(state.config.afk_timeout_ms);
+
+    // STATS packets sent before the game answered the last one are ignored,
+    // so a client asking quickly doesn't pile up requests
+    let mut stats_requested = false;
+
+    // the connection is closed with this error after leaving the loop
+    let mut error = None;
+    let mut shutting_down = false;
+
+    while !disconnected {
+        tokio::select! {
+            biased;
+
+            packet = socket.recv() => {
+                let packet = match packet {
+                    None | Some(Err(_)) => {
+                        println!("A websocket connection produced a error (probably abruptly closed)...");
+                        disconnected = true;
+                        break;
+                    }
+                    Some(Ok(packet)) => packet,
+                };
+                let packet = match packet {
+                    axum::extract::ws::Message::Close(_) => {
+                        println!("Client leaved...");
+                        break;
+                    }
+                    axum::extract::ws::Message::Text(text) => text,
+                    _ => {
+                        println!("Received unexpected non-text packet from client...");
+                        error = Some((ErrorCode::NotText, "Only text messages are supported".to_string()));
+                        break;
+                    }
+                };
+
+                let packet = match wire_format.decode(&packet) {
+                    Err(err) => {
+                        println!("A websocket connection sent a packet expected to be a MOVE but failed parsing:\n\t{}", err);
+                        error = Some((ErrorCode::BadPacket, err.to_string()));
+                        break;
+                    }
+                    Ok(packet) => packet,
+                };
+
+                match packet {
+                    ClientPacket::PacketOLLEH(..) | ClientPacket::PacketRESUME(..) => {
+                        println!("A websocket connection sent a packet expected to be a MOVE but is a {:?}", packet);
+                        error = Some((ErrorCode::UnexpectedPacket, "Already joined the game".to_string()));
+                        break;
+                    }
+                    ClientPacket::PacketMOVE(index, action) => {
+                        if index as usize >= bomb_counter.len() {
+                            println!("A websocket connection sent a MOVE packet with a index out of bound");
+                            error = Some((ErrorCode::BadBombIndex, format!("Bomb index {index} is out of range")));
+                            break;
+                        }
+                        match bomb_actions.get_mut(index as usize).and_then(Option::take) {
+                            Some(action_sender) => {
+                                // the round may have ended before the game got the move
+                                let _ = action_sender.send(Ok(action));
+                                bomb_counter[index as usize]+=1;
+                            }
+                            None => {
+                                // the bomb can be taken away (AFK, end of round, removed) while the MOVE is on its way
+                                println!("A websocket connection sent a MOVE packet while not holding the specified bomb");
+                                send_error(&mut socket, wire_format, &negotiated, ErrorCode::BombNotHeld, &format!("Not holding bomb {index}")).await;
+                            }
+                        }
+                    }
+                    ClientPacket::PacketSTART | ClientPacket::PacketSTATS => {
+                        let event = match packet {
+                            ClientPacket::PacketSTART => PlayerEvent::Start,
+                            _ if stats_requested => continue,
+                            _ => {
+                                stats_requested = true;
+                                PlayerEvent::Stats
+                            }
+                        };
+                        // the game only ends before the players leave when the server shuts down
+                        if player_event_notify.send((player_id, event)).await.is_err() {
+                            println!("The game of a player is gone, closing their connection...");
+                            shutting_down = true;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            update = update_receiver.recv() => {
+                let update = match update {
+                    Some(update) => update,
+                    // the game ended without saying goodbye
+                    None => break,
+                };
+                let packet = match update {
+                    // clients without the bombs capability only see the bombs HELLO told them about,
+                    // the game doesn't hand them the bombs added after that
+                    GameUpdate::Player(PlayerUpdate::BombMoved(index, position)) => {
+                        bomb_actions[index as usize] = None;
+                        (index < bomb_count || negotiated.supports(CAP_BOMBS))
+                            .then_some(ServerPacket::PacketSTATUS(index, position))
+                    }
+                    GameUpdate::BombReceived(index, action_sender) => {
+                        bomb_actions[index as usize] = Some(action_sender);
+                        let current_count = bomb_counter[index as usize];
+                        wait_bomb_timeout.spawn(async move {
+                            tokio::time::sleep(afk_timeout).await;
+                            (current_count, index)
+                        });
+                        (index < bomb_count || negotiated.supports(CAP_BOMBS))
+                            .then_some(ServerPacket::PacketSTATUS(index, BombPosition::X))
+                    },
+                    // older clients only see the bombs, the game starts and ends without them knowing
+                    GameUpdate::Player(PlayerUpdate::Lobby(min_players, lobby)) => {
+                        negotiated.supports(CAP_LOBBY).then_some(ServerPacket::PacketLOBBY(min_players, lobby))
+                    }
+                    GameUpdate::Player(PlayerUpdate::Begin) => {
+                        negotiated.supports(CAP_LOBBY).then_some(ServerPacket::PacketBEGIN)
+                    }
+                    GameUpdate::Player(PlayerUpdate::End(cooldown, ranking)) => {
+                        bomb_actions.iter_mut().for_each(|action| *action = None);
+                        bomb_counter.iter_mut().for_each(|counter| *counter += 1);
+                        negotiated.supports(CAP_ROUNDS).then_some(ServerPacket::PacketEND(cooldown, ranking))
+                    }
+                    GameUpdate::Player(PlayerUpdate::Stats(players_stats)) => {
+                        stats_requested = false;
+                        negotiated.supports(CAP_STATS).then_some(ServerPacket::PacketSTATS(players_stats))
+                    }
+                    GameUpdate::Player(PlayerUpdate::BombCount(new_bomb_count)) => {
+                        // the game gets a closed channel for removed bombs the player was holding
+                        bomb_actions.resize_with(new_bomb_count as usize, || Option::None);
+                        bomb_counter
+                            .iter_mut()
+                            .skip(new_bomb_count as usize)
+                            .for_each(|counter| *counter += 1);
+                        if bomb_counter.len() < new_bomb_count as usize {
+                            bomb_counter.resize(new_bomb_count as usize, 0);
+                        }
+                        negotiated.supports(CAP_BOMBS).then_some(ServerPacket::PacketBOMBS(new_bomb_count))
+                    }
+                    GameUpdate::Player(PlayerUpdate::Leave) => {
+                        println!("The game asked the player to leave...");
+                        break;
+                    }
+                    GameUpdate::Player(PlayerUpdate::Shutdown(board)) => {
+                        let packet = if negotiated.supports(CAP_SHUTDOWN) {
+                            ServerPacket::PacketSHUTDOWN(board)
+                        } else {
+                            ServerPacket::PacketBOARD(board)
+                        };
+                        let _ = socket.send(wire_format.encode(&packet)).await;
+                        shutting_down = true;
+                        break;
+                    }
+                };
+                if let Some(packet) = packet {
+                    if socket.send(wire_format.encode(&packet)).await.is_err() {
+                        println!("A websocket connection can't be sent to anymore (probably abruptly closed)...");
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+
+            timeout_result = wait_bomb_timeout.join_next(), if !wait_bomb_timeout.is_empty() => {
+                let (counter, bomb_index) = timeout_result.unwrap().unwrap();
+                if bomb_counter[bomb_index as usize] == counter {
+                    let _ = bomb_actions[bomb_index as usize]
+                        .take()
+                        .unwrap()
+                        .send(Err(()));
+                    bomb_counter[bomb_index as usize] += 1;
+                }
+            }
+
+            _ = scoreboard_receiver.changed() => {
+                let board = {
+                    scoreboard_receiver.borrow().to_string()
+                };
+                if socket.send(wire_format.encode(&ServerPacket::PacketBOARD(board))).await.is_err() {
+                    println!("A websocket connection can't be sent to anymore (probably abruptly closed)...");
+                    disconnected = true;
+                    break;
+                }
+            }
+        };
+    }
+
+    let event = if disconnected {
+        PlayerEvent::Disconnect
+    } else {
+        PlayerEvent::Leave
+    };
+    // the game is already gone if it ended the connection
+    let _ = player_event_notify.send((player_id, event)).await;
+    match error {
+        _ if shutting_down => close_for_shutdown(&mut socket).await,
+        Some((code, reason)) => {
+            send_error(&mut socket, wire_format, &negotiated, code, &reason).await
+        }
+        None => {
+            let _ = socket
+                .send(axum::extract::ws::Message::Close(Option::None))
+                .await;
+        }
+    }
+    // the game can still send updates before it handles the leave
+    while update_receiver.recv().await.is_some() {}
+}
+
+// Spectators get a VIEW packet whenever the game changes, and can't send anything to it
+async fn spectator_handler(
+    mut socket: ws::WebSocket,
+    state: AppState,
+    room: RoomName,
+    mut view_rx: tokio::sync::watch::Receiver<SpectatorView>,
+) {
+    let wire_format = socket
+        .protocol()
+        .and_then(|protocol| protocol.to_str().ok())
+        .and_then(WireFormat::from_protocol)
+        .unwrap_or_default();
+    println!("A spectator started watching room {room}...");
+    // spectators skip the handshake, they are treated as clients knowing everything
+    let negotiated = Negotiated::latest();
+    let mut shutdown = state.shutdown.subscribe();
+
+    // the current view first, then every change
+    let mut view = Some(view_rx.borrow_and_update().clone());
+    loop {
+        if let Some((phase, holders, ring)) = view.take() {
+            let packet = ServerPacket::PacketVIEW(phase, holders, ring);
+            if socket.send(wire_format.encode(&packet)).await.is_err() {
+                println!("A spectator connection produced a error (probably abruptly closed)...");
+                return;
+            }
+        }
+
+        tokio::select! {
+            _ = shutdown.changed() => {
+                close_for_shutdown(&mut socket).await;
+                return;
+            }
+
+            packet = socket.recv() => match packet {
+                None | Some(Err(_)) | Some(Ok(ws::Message::Close(_))) => {
+                    println!("A spectator stopped watching room {room}...");
+                    return;
+                }
+                Some(Ok(ws::Message::Ping(_))) | Some(Ok(ws::Message::Pong(_))) => {}
+                Some(Ok(ws::Message::Text(_))) => {
+                    println!("A spectator sent a packet...");
+                    send_error(
+                        &mut socket,
+                        wire_format,
+                        &negotiated,
+                        ErrorCode::UnexpectedPacket,
+                        "Spectators can't send packets",
+                    )
+                    .await;
+                    return;
+                }
+                Some(Ok(ws::Message::Binary(_))) => {
+                    send_error(
+                        &mut socket,
+                        wire_format,
+                        &negotiated,
+                        ErrorCode::NotText,
+                        "Only text messages are supported",
+                    )
+                    .await;
+                    return;
+                }
+            },
+
+            changed = view_rx.changed() => {
+                if changed.is_err() {
+                    let _ = socket
+                        .send(ws::Message::Close(Some(ws::CloseFrame {
+                            code: ws::close_code::NORMAL,
+                            reason: "The game in this room ended".into(),
+                        })))
+                        .await;
+                    return;
+                }
+                view = Some(view_rx.borrow().clone());
+            }
+        }
+    }
+}
+
+// A computer player, joins the game the same way a websocket client does
+// the seat, reaction times and passes of a bot all come from its seed
+async fn bot_player(
+    olleh_tx: OllehSender,
+    behavior: BotBehavior,
+    afk_timeout: tokio::time::Duration,
+    seed: u64,
+) {
+    let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(seed);
+    let (request_result_tx, request_result_rx) = tokio::sync::oneshot::channel();
+    if olleh_tx
+        .send((
+            JoinRequest::Olleh(rand::Rng::gen::<u8>(&mut rng) as PreferredID, None, None),
+            request_result_tx,
+        ))
+        .is_err()
+    {
+        return;
+    }
+    let (player_id, (player_name, _), _, _, mut update_receiver, _, player_event_notify) =
+        match request_result_rx.await {
+            Ok(join_result) => join_result,
+            Err(_) => return,
+        };
+    println!("Bot {player_name} joined as {player_id}...");
+
+    // bomb index -> the answer the bot is going to give and the channel to give it to
+    let mut bomb_actions = std::collections::BTreeMap::<
+        BombIndex,
+        (
+            Result<BombMoveAction, ()>,
+            tokio::sync::oneshot::Sender<Result<BombMoveAction, ()>>,
+        ),
+    >::new();
+    let mut wait_bomb_reaction = tokio::task::JoinSet::<(u32, BombIndex)>::new();
+    let mut bomb_counter = std::collections::BTreeMap::<BombIndex, u32>::new();
+
+    loop {
+        tokio::select! {
+            update = update_receiver.recv() => {
+                let update = match update {
+                    Some(update) => update,
+                    None => break,
+                };
+                match update {
+                    GameUpdate::Player(PlayerUpdate::BombMoved(index, _)) => {
+                        bomb_actions.remove(&index);
+                        *bomb_counter.entry(index).or_insert(0) += 1;
+                    }
+                    GameUpdate::BombReceived(index, action_sender) => {
+                        // slow bots end up being AFK just like players do
+                        let reaction_time = behavior.reaction_time(&mut rng);
+                        let (action, wait_time) = if reaction_time < afk_timeout {
+                            (Ok(rand::Rng::gen::<BombMoveAction>(&mut rng)), reaction_time)
+                        } else {
+                            (Err(()), afk_timeout)
+                        };
+                        bomb_actions.insert(index, (action, action_sender));
+                        let counter = bomb_counter.entry(index).or_insert(0);
+                        *counter += 1;
+                        let current_count = *counter;
+                        wait_bomb_reaction.spawn(async move {
+                            tokio::time::sleep(wait_time).await;
+                            (current_count, index)
+                        });
+                    }
+                    GameUpdate::Player(PlayerUpdate::End(_, _)) => {
+                        bomb_actions.clear();
+                    }
+                    // dropping the channels of removed bombs tells the game they're gone
+                    GameUpdate::Player(PlayerUpdate::BombCount(bomb_count)) => {
+                        bomb_actions.retain(|index, _| *index < bomb_count);
+                    }
+                    GameUpdate::Player(
+                        PlayerUpdate::Lobby(_, _) | PlayerUpdate::Begin | PlayerUpdate::Stats(_),
+                    ) => {}
+                    GameUpdate::Player(PlayerUpdate::Leave | PlayerUpdate::Shutdown(_)) => break,
+                }
+            }
+
+            reaction_result = wait_bomb_reaction.join_next(), if !wait_bomb_reaction.is_empty() => {
+                let (counter, bomb_index) = reaction_result.unwrap().unwrap();
+                // the bomb may have been taken away and given back since the timer started
+                if bomb_counter[&bomb_index] == counter {
+                    if let Some((action, action_sender)) = bomb_actions.remove(&bomb_index) {
+                        let _ = action_sender.send(action);
+                    }
+                }
+            }
+        }
+    }
+
+    // bombs the bot didn't answer for are handed to other players by the game
+    drop(bomb_actions);
+    let _ = player_event_notify
+        .send((player_id, PlayerEvent::Leave))
+        .await;
+    // the game can still send updates before it handles the leave
+    while update_receiver.recv().await.is_some() {}
+}
+
+// How a game talks to the rest of the server, besides the players it's given
+struct GameContext {
+    room: RoomName,
+    shutdown: tokio::sync::watch::Receiver<bool>,
+    // the game publishes its state here for spectators
+    view_tx: tokio::sync::watch::Sender<SpectatorView>,
+    admin_rx: tokio::sync::mpsc::Receiver<AdminCommand>,
+    // round results for the leaderboard
+    results_tx: tokio::sync::mpsc::Sender<RoundResults>,
+    // the game keeps the names of its players in here
+    live_names: LiveNames,
+    // every game writes its events to a new file in here
+    event_log_dir: Option<std::path::PathBuf>,
+    // the seed of the first game, the next ones get theirs from the game before them
+    seed: u64,
+    clock: std::sync::Arc<dyn Clock>,
+}
+
+// Drives a GameState: feeds it the requests, player events, bomb answers and timers of the room
+// and carries out what it returns
+async fn game_server(
+    mut game_request_rx: tokio::sync::mpsc::Receiver<GameRequest>,
+    config: GameConfig,
+    scoring: Box<dyn ScoringPolicy>,
+    context: GameContext,
+) {
+    println!("Server Started");
+    let GameContext {
+        room,
+        mut shutdown,
+        view_tx,
+        mut admin_rx,
+        results_tx,
+        live_names,
+        event_log_dir,
+        seed,
+        clock,
+    } = context;
+
+    let mut driver = GameDriver {
+        players_channel: std::collections::BTreeMap::new(),
+        wait_bomb_action: tokio::task::JoinSet::new(),
+        wait_olleh: tokio::task::JoinSet::new(),
+        events: EventLog::disabled(),
+        results_tx,
+        bot_behavior: BotBehavior::new(
+            config.bot_difficulty,
+            config.bot_reaction_mean_ms,
+            config.bot_reaction_std_dev_ms,
+        ),
+        afk_timeout: tokio::time::Duration::from_millis(config.afk_timeout_ms),
+    };
+    let mut game = GameState::new(config, scoring, seed, tokio::time::Instant::now());
+    loop {
+        // every game (player IDs start from 0 again) gets its own log
+        driver.events = match &event_log_dir {
+            Some(dir) => {
+                EventLog::create(dir, &room, game.seed(), clock.as_ref()).unwrap_or_else(|err| {
+                    println!("Failed creating the event log of room {room}: {err}");
+                    EventLog::disabled()
+                })
+            }
+            None => EventLog::disabled(),
+        };
+        // answers from the last game can't be told apart from the handoffs of this one
+        driver.wait_bomb_action = tokio::task::JoinSet::new();
+
+        let (scoreboard_watch_tx, scoreboard_watch_rx) =
+            tokio::sync::watch::channel("".to_string());
+        let (player_event_tx, mut player_event_rx) = tokio::sync::mpsc::channel(32);
+
+        loop {
+            let deadline = game.next_deadline();
+            // set when a player may have been the last one in the game
+            let mut gone = false;
+            let effects = tokio::select! {
+                biased;
+
+                _ = shutdown.changed() => {
+                    println!("Shutting down the game...");
+                    driver.apply(game.shutdown()).await;
+                    driver.events.close().await;
+                    return;
+                }
+
+                player_event = player_event_rx.recv() => {
+                    let (player_id, event) = player_event.unwrap();
+                    let now = tokio::time::Instant::now();
+                    match event {
+                        PlayerEvent::Stats => game.stats(player_id),
+                        PlayerEvent::Start => game.start(player_id, now),
+                        PlayerEvent::Disconnect => {
+                            driver.players_channel.remove(&player_id);
+                            gone = true;
+                            game.disconnect(player_id, now)
+                        }
+                        PlayerEvent::Leave => {
+                            driver.players_channel.remove(&player_id);
+                            gone = true;
+                            game.leave(player_id, now)
+                        }
+                    }
+                }
+
+                action_result = driver.wait_bomb_action.join_next(), if !driver.wait_bomb_action.is_empty() => {
+                    let (handoff, action) = action_result.unwrap().unwrap();
+                    let now = tokio::time::Instant::now();
+                    match action {
+                        Ok(Ok(action)) => game.pass(handoff, action, now),
+                        Ok(Err(())) => game.timeout(handoff, now),
+                        Err(_) => game.drop_handoff(handoff, now),
+                    }
+                }
+
+                ollehed_request = driver.wait_olleh.join_next(), if !driver.wait_olleh.is_empty() => {
+                    let now = tokio::time::Instant::now();
+                    match ollehed_request.unwrap().unwrap() {
+                        (_, Err(_)) => {
+                            println!("A game request closed before returning OLLEH result...");
+                            if game.is_abandoned() && driver.wait_olleh.is_empty() {
+                                // nobody is joining the room anymore
+                                return;
+                            }
+                            Vec::new()
+                        }
+                        (_, Ok((JoinRequest::Resume(resume_token, bomb_limit), request_response))) => {
+                            // dropping the result sender tells the client it can't resume
+                            let Some((player_id, player_data, effects)) = game.resume(&resume_token, bomb_limit, now) else {
+                                println!("A player tried to resume with a bad token...");
+                                if game.is_abandoned() && driver.wait_olleh.is_empty() {
+                                    return;
+                                }
+                                continue;
+                            };
+                            let (player_status_tx, player_status_rx) = tokio::sync::mpsc::channel(PLAYER_CHANNEL_SIZE);
+                            request_response
+                                .send((
+                                    player_id,
+                                    player_data,
+                                    resume_token,
+                                    game.bomb_count(),
+                                    player_status_rx,
+                                    scoreboard_watch_rx.clone(),
+                                    player_event_tx.clone(),
+                                ))
+                                .unwrap();
+                            driver.players_channel.insert(player_id, player_status_tx);
+                            effects
+                        }
+                        (is_bot, Ok((JoinRequest::Olleh(preferred_id, requested_data, bomb_limit), request_response))) => {
+                            let resume_token = random_resume_token();
+                            // the names of the other rooms stay locked until this room's new name is in
+                            let (player_id, player_data, effects) = {
+                                let mut live_names = live_names.lock().unwrap();
+                                game.reserve_names(
+                                    live_names
+                                        .iter()
+                                        .filter(|(other_room, _)| **other_room != room)
+                                        .flat_map(|(_, names)| names.iter().cloned())
+                                        .collect(),
+                                );
+                                let joined = game.join(preferred_id, requested_data, is_bot, bomb_limit, resume_token.clone(), now);
+                                live_names.insert(room.clone(), game.player_names());
+                                joined
+                            };
+                            let (player_status_tx, player_status_rx) = tokio::sync::mpsc::channel(PLAYER_CHANNEL_SIZE);
+                            request_response
+                                .send((
+                                    player_id,
+                                    player_data,
+                                    resume_token,
+                                    game.bomb_count(),
+                                    player_status_rx,
+                                    scoreboard_watch_rx.clone(),
+                                    player_event_tx.clone(),
+                                ))
+                                .unwrap();
+                            driver.players_channel.insert(player_id, player_status_tx);
+                            effects
+                        }
+                    }
+                }
+
+                Some(command) = admin_rx.recv() => {
+                    let now = tokio::time::Instant::now();
+                    match command {
+                        AdminCommand::Inspect(info_tx) => {
+                            let _ = info_tx.send(game.room_info());
+                            Vec::new()
+                        }
+                        AdminCommand::Kick(player_id, kicked_tx) => {
+                            let (kicked, effects) = game.kick(player_id, now);
+                            let _ = kicked_tx.send(kicked);
+                            effects
+                        }
+                        AdminCommand::ResetScores => game.reset_scores(),
+                        AdminCommand::Pause => game.pause(now),
+                        AdminCommand::Resume => game.unpause(now),
+                        AdminCommand::SetBombCount(bomb_count) => game.set_bomb_count(bomb_count, now),
+                    }
+                }
+
+                new_request = game_request_rx.recv() => {
+                    let new_request = new_request.unwrap();
+                    let (wait_olleh_tx, wait_olleh_rx) = tokio::sync::oneshot::channel();
+                    new_request.send((game.bomb_count(), wait_olleh_tx)).unwrap();
+                    driver.wait_olleh.spawn(async move { (false, wait_olleh_rx.await) });
+                    Vec::new()
+                }
+
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {
+                    gone = true;
+                    game.tick(tokio::time::Instant::now())
+                }
+            };
+            driver.apply(effects).await;
+
+            if gone && game.is_abandoned() {
+                println!("All players leaved...");
+                if driver.wait_olleh.is_empty() {
+                    driver.events.close().await;
+                    return;
+                }
+                break;
+            }
+            publish(&scoreboard_watch_tx, game.scoreboard());
+            publish(&view_tx, game.spectator_view());
+            live_names
+                .lock()
+                .unwrap()
+                .insert(room.clone(), game.player_names());
+        }
+        std::mem::replace(&mut driver.events, EventLog::disabled())
+            .close()
+            .await;
+        game = game.restart(tokio::time::Instant::now());
+    }
+}
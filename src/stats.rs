@@ -0,0 +1,45 @@
+use crate::packet::PlayerStats;
+
+// Collects what a player does with the bombs they get, kept by the game for every player
+#[derive(Debug, Clone, Default)]
+pub struct StatsTracker {
+    // milliseconds held before every pass, kept sorted for the median
+    reaction_times: Vec<u32>,
+    reaction_total: u64,
+    timeouts: u32,
+    bombs_received: u32,
+    longest_hold: std::time::Duration,
+}
+
+impl StatsTracker {
+    pub fn on_received(&mut self) {
+        self.bombs_received += 1;
+    }
+
+    pub fn on_pass(&mut self, held: std::time::Duration) {
+        let held_ms = held.as_millis().min(u32::MAX as u128) as u32;
+        let index = self.reaction_times.partition_point(|time| *time < held_ms);
+        self.reaction_times.insert(index, held_ms);
+        self.reaction_total += held_ms as u64;
+        self.longest_hold = self.longest_hold.max(held);
+    }
+
+    pub fn on_timeout(&mut self, held: std::time::Duration) {
+        self.timeouts += 1;
+        self.longest_hold = self.longest_hold.max(held);
+    }
+
+    pub fn summary(&self) -> PlayerStats {
+        let passes = self.reaction_times.len();
+        PlayerStats {
+            passes: passes as u32,
+            timeouts: self.timeouts,
+            bombs_received: self.bombs_received,
+            best_reaction_ms: self.reaction_times.first().copied(),
+            mean_reaction_ms: (passes > 0).then(|| (self.reaction_total / passes as u64) as u32),
+            // the lower one of the two middle times for an even amount of passes
+            median_reaction_ms: (passes > 0).then(|| self.reaction_times[(passes - 1) / 2]),
+            longest_hold_ms: self.longest_hold.as_millis().min(u32::MAX as u128) as u32,
+        }
+    }
+}
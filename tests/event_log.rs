@@ -0,0 +1,98 @@
+// Plays the same game twice with tokio's time paused and compares the event logs it writes,
+// and checks the names of the log files.
+
+use multi_bomb_test::clock::FixedClock;
+use multi_bomb_test::config::GameConfig;
+use multi_bomb_test::events::EventLog;
+use multi_bomb_test::game::{Effect, GameState, HandoffID};
+use multi_bomb_test::packet::BombMoveAction;
+use multi_bomb_test::scoring::scoring_policy;
+use tokio::time::{Duration, Instant};
+
+// logs what the game did and returns the handoffs of the bombs it handed out
+fn apply(log: &mut EventLog, effects: Vec<Effect>) -> Vec<HandoffID> {
+    let mut handoffs = Vec::new();
+    for effect in effects {
+        match effect {
+            Effect::Log(event) => log.log(event),
+            Effect::GiveBomb(_, _, handoff) => handoffs.push(handoff),
+            _ => {}
+        }
+    }
+    handoffs
+}
+
+// players with random names pass the bomb, wait and hold it too long, returns the event log
+async fn play(dir: &std::path::Path, seed: u64) -> String {
+    std::fs::create_dir_all(dir).unwrap();
+    let clock = FixedClock::new(std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let config = GameConfig {
+        bomb_count: 1,
+        lobby_min_players: 3,
+        afk_timeout_ms: 1000,
+        ..GameConfig::default()
+    };
+    let scoring = scoring_policy(config.scoring, &config);
+    let mut game = GameState::new(config, scoring, seed, Instant::now());
+    let mut log = EventLog::create(dir, "room", game.seed(), &clock).unwrap();
+
+    let mut handoffs = Vec::new();
+    for preferred_id in 0..3 {
+        let (_, _, effects) = game.join(
+            preferred_id,
+            None,
+            false,
+            None,
+            format!("token-{preferred_id}"),
+            Instant::now(),
+        );
+        handoffs.extend(apply(&mut log, effects));
+    }
+    for action in [BombMoveAction::R1, BombMoveAction::L3, BombMoveAction::R2] {
+        tokio::time::advance(Duration::from_millis(300)).await;
+        let effects = game.pass(handoffs.pop().unwrap(), action, Instant::now());
+        handoffs.extend(apply(&mut log, effects));
+    }
+    tokio::time::advance(Duration::from_millis(1000)).await;
+    let effects = game.timeout(handoffs.pop().unwrap(), Instant::now());
+    apply(&mut log, effects);
+    log.close().await;
+
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+        .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn the_same_seed_and_clock_write_the_same_event_log() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("event_log");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let first = play(&dir.join("first"), 7).await;
+    let second = play(&dir.join("second"), 7).await;
+    assert!(first.contains("\"start\""));
+    assert_eq!(first, second);
+    assert_ne!(first, play(&dir.join("other"), 8).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn games_starting_in_the_same_millisecond_get_their_own_log() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("same_millisecond");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let clock = FixedClock::new(std::time::UNIX_EPOCH);
+    for seed in 0..3 {
+        EventLog::create(&dir, "room", seed, &clock)
+            .unwrap()
+            .close()
+            .await;
+    }
+    let mut names = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["room-0-1.jsonl", "room-0-2.jsonl", "room-0.jsonl"]);
+}
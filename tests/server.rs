@@ -0,0 +1,478 @@
+// Starts the real router on an ephemeral port and plays games through scripted websocket clients.
+// Passes are worth a fixed amount of points, so the packets are the same on every run.
+
+use futures_util::{SinkExt, StreamExt};
+use multi_bomb_test::client::{self, ClientError, ClientOptions};
+use multi_bomb_test::clock::FixedClock;
+use multi_bomb_test::config::GameConfig;
+use multi_bomb_test::leaderboard::Leaderboard;
+use multi_bomb_test::packet::{
+    ErrorCode, ServerPacket, WireFormat, PROTOCOL_VERSION, SERVER_CAPABILITIES,
+};
+use multi_bomb_test::scoring::ScoringKind;
+use multi_bomb_test::server::{router, AppState};
+use tokio_tungstenite::tungstenite::Message;
+
+// longer than the AFK timeout, a missing packet fails the test instead of hanging it
+const RECV_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(10);
+
+const COLOR: &str = "#000000";
+
+struct TestServer {
+    addr: std::net::SocketAddr,
+    // the games shut down once every AppState is gone
+    _state: AppState,
+}
+
+impl TestServer {
+    async fn start(config: GameConfig) -> TestServer {
+        let (state, _running) = AppState::new(
+            config,
+            None,
+            Leaderboard::default(),
+            None,
+            None,
+            std::sync::Arc::new(FixedClock::new(std::time::UNIX_EPOCH)),
+        );
+        let assets_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+            .serve(router(state.clone(), &assets_dir).into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        TestServer {
+            addr,
+            _state: state,
+        }
+    }
+
+    async fn connect(&self) -> TestClient {
+        self.connect_to("/ws").await
+    }
+
+    async fn connect_to(&self, path: &str) -> TestClient {
+        let (socket, _) = tokio_tungstenite::connect_async(format!("ws://{}{path}", self.addr))
+            .await
+            .unwrap();
+        TestClient { socket }
+    }
+
+    // joins like a client from before protocol versions, which only gets NAME, BOARD and STATUS
+    async fn join(&self, preferred_id: u32, name: &str) -> TestClient {
+        let mut client = self.connect().await;
+        client
+            .expect("hello\n1\n2 lobby rounds resume error shutdown bombs stats")
+            .await;
+        client
+            .send(&format!("olleh\n{preferred_id}\n{name}\n{COLOR}"))
+            .await;
+        client.expect(&format!("name\n{name}\n{COLOR}")).await;
+        client
+    }
+
+    // seats the players in the order of the names, the game starts with the last one
+    // and the first one gets the bomb
+    async fn start_game(&self, names: &[&str]) -> Vec<TestClient> {
+        let mut clients = Vec::new();
+        for (preferred_id, name) in names.iter().enumerate() {
+            clients.push(self.join(preferred_id as u32, name).await);
+        }
+        // everyone sees the scoreboard grow with every player joining after them
+        let boards = (1..=names.len())
+            .map(|joined| {
+                board(
+                    &names[..joined]
+                        .iter()
+                        .rev()
+                        .map(|name| (*name, 0))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        for (seat, client) in clients.iter_mut().enumerate() {
+            for board in &boards[seat..boards.len() - 1] {
+                client.expect(board).await;
+            }
+            let position = if seat == 0 { "X" } else { "L" };
+            client.expect(&format!("status\n0 {position}")).await;
+            client.expect(boards.last().unwrap()).await;
+        }
+        clients
+    }
+}
+
+struct TestClient {
+    socket: tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+}
+
+impl TestClient {
+    async fn send(&mut self, text: &str) {
+        self.socket
+            .send(Message::Text(text.to_string()))
+            .await
+            .unwrap();
+    }
+
+    async fn recv(&mut self) -> String {
+        let message = tokio::time::timeout(RECV_TIMEOUT, self.socket.next())
+            .await
+            .expect("no packet from the server")
+            .expect("connection closed")
+            .unwrap();
+        match message {
+            Message::Text(text) => text,
+            message => panic!("expected a text packet, got {:?}", message),
+        }
+    }
+
+    async fn expect(&mut self, text: &str) {
+        assert_eq!(self.recv().await, text);
+    }
+}
+
+fn test_config(lobby_min_players: u32) -> GameConfig {
+    GameConfig {
+        bomb_count: 1,
+        lobby_min_players,
+        // how long a player holds the bomb doesn't change the score
+        scoring: ScoringKind::HotPotato,
+        score_base: 1000,
+        hold_cost_per_sec: 0,
+        streak_bonus: 0,
+        timeout_penalty: 600,
+        // a dropped connection leaves right away
+        resume_grace_ms: 0,
+        seed: Some(1),
+        ..GameConfig::default()
+    }
+}
+
+// the BOARD of the players, highest score first
+fn board(players: &[(&str, u32)]) -> String {
+    let mut board = String::from("board\n");
+    for (name, score) in players {
+        board.push_str(&format!("{name}\n{COLOR}\n{score}\n"));
+    }
+    board
+}
+
+// every client sees where the bomb went, one position for every client in seat order,
+// and then the new scoreboard
+async fn expect_move(clients: &mut [TestClient], positions: &str, scoreboard: &str) {
+    for (client, position) in clients.iter_mut().zip(positions.chars()) {
+        client.expect(&format!("status\n0 {position}")).await;
+        client.expect(scoreboard).await;
+    }
+}
+
+#[tokio::test]
+async fn joining_players_get_their_name_the_scoreboard_and_the_bomb() {
+    let server = TestServer::start(test_config(2)).await;
+
+    let mut alice = server.join(0, "Alice").await;
+    alice.expect(&board(&[("Alice", 0)])).await;
+
+    let mut bob = server.join(1, "Bob").await;
+    alice.expect("status\n0 X").await;
+    alice.expect(&board(&[("Bob", 0), ("Alice", 0)])).await;
+    bob.expect("status\n0 L").await;
+    bob.expect(&board(&[("Bob", 0), ("Alice", 0)])).await;
+}
+
+#[tokio::test]
+async fn passing_the_bomb_with_every_action() {
+    let server = TestServer::start(test_config(6)).await;
+    let mut clients = server.start_game(&["A", "B", "C", "D", "E", "F"]).await;
+
+    clients[0].send("move\n0 R1").await;
+    expect_move(
+        &mut clients,
+        "RXLLLL",
+        &board(&[
+            ("A", 1000),
+            ("F", 0),
+            ("E", 0),
+            ("D", 0),
+            ("C", 0),
+            ("B", 0),
+        ]),
+    )
+    .await;
+
+    clients[1].send("move\n0 R2").await;
+    expect_move(
+        &mut clients,
+        "RRRXLL",
+        &board(&[
+            ("B", 1000),
+            ("A", 1000),
+            ("F", 0),
+            ("E", 0),
+            ("D", 0),
+            ("C", 0),
+        ]),
+    )
+    .await;
+
+    clients[3].send("move\n0 L1").await;
+    expect_move(
+        &mut clients,
+        "RRXLLL",
+        &board(&[
+            ("D", 1000),
+            ("B", 1000),
+            ("A", 1000),
+            ("F", 0),
+            ("E", 0),
+            ("C", 0),
+        ]),
+    )
+    .await;
+
+    // wraps around to the last seat
+    clients[2].send("move\n0 L3").await;
+    expect_move(
+        &mut clients,
+        "RRRRRX",
+        &board(&[
+            ("D", 1000),
+            ("C", 1000),
+            ("B", 1000),
+            ("A", 1000),
+            ("F", 0),
+            ("E", 0),
+        ]),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn holding_the_bomb_too_long_passes_it_right() {
+    let server = TestServer::start(GameConfig {
+        afk_timeout_ms: 200,
+        ..test_config(3)
+    })
+    .await;
+    let mut clients = server.start_game(&["A", "B", "C"]).await;
+
+    clients[0].send("move\n0 R1").await;
+    expect_move(
+        &mut clients,
+        "RXL",
+        &board(&[("A", 1000), ("C", 0), ("B", 0)]),
+    )
+    .await;
+    clients[1].send("move\n0 L1").await;
+    expect_move(
+        &mut clients,
+        "XLL",
+        &board(&[("B", 1000), ("A", 1000), ("C", 0)]),
+    )
+    .await;
+
+    // how long the timeout takes is covered by the unit tests of GameState
+    expect_move(
+        &mut clients,
+        "RXL",
+        &board(&[("B", 1000), ("A", 400), ("C", 0)]),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn disconnecting_with_the_bomb_passes_it_right() {
+    let server = TestServer::start(test_config(3)).await;
+    let mut clients = server.start_game(&["A", "B", "C"]).await;
+
+    clients[0].send("move\n0 R1").await;
+    expect_move(
+        &mut clients,
+        "RXL",
+        &board(&[("A", 1000), ("C", 0), ("B", 0)]),
+    )
+    .await;
+
+    // the connection drops without a close frame
+    drop(clients.remove(1));
+    expect_move(&mut clients, "RX", &board(&[("A", 1000), ("C", 0)])).await;
+}
+
+#[tokio::test]
+async fn dropped_players_pass_their_bomb_on_and_keep_their_score() {
+    let server = TestServer::start(GameConfig {
+        resume_grace_ms: 60000,
+        ..test_config(3)
+    })
+    .await;
+    let mut clients = server.start_game(&["A", "B", "C"]).await;
+
+    clients[0].send("move\n0 R1").await;
+    expect_move(
+        &mut clients,
+        "RXL",
+        &board(&[("A", 1000), ("C", 0), ("B", 0)]),
+    )
+    .await;
+
+    // the connection drops without a close frame while the others keep playing
+    drop(clients.remove(1));
+    clients[0].expect("status\n0 R").await;
+    clients[1].expect("status\n0 X").await;
+
+    // B keeps their score while they can come back
+    clients[1].send("move\n0 R1").await;
+    expect_move(
+        &mut clients,
+        "XL",
+        &board(&[("C", 1000), ("A", 1000), ("B", 0)]),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn players_asking_for_the_same_id_sit_next_to_each_other() {
+    let server = TestServer::start(test_config(3)).await;
+
+    let mut alice = server.join(5, "Alice").await;
+    alice.expect(&board(&[("Alice", 0)])).await;
+    let mut bob = server.join(5, "Bob").await;
+    alice.expect(&board(&[("Bob", 0), ("Alice", 0)])).await;
+    bob.expect(&board(&[("Bob", 0), ("Alice", 0)])).await;
+
+    // sits before both of them and gets the bomb
+    let mut carol = server.join(0, "Carol").await;
+    let scoreboard = board(&[("Carol", 0), ("Bob", 0), ("Alice", 0)]);
+    alice.expect("status\n0 L").await;
+    alice.expect(&scoreboard).await;
+    bob.expect("status\n0 L").await;
+    bob.expect(&scoreboard).await;
+    carol.expect("status\n0 X").await;
+    carol.expect(&scoreboard).await;
+
+    // seats: Carol, Alice, Bob
+    let mut clients = [carol, alice, bob];
+    clients[0].send("move\n0 R1").await;
+    expect_move(
+        &mut clients,
+        "RXL",
+        &board(&[("Carol", 1000), ("Bob", 0), ("Alice", 0)]),
+    )
+    .await;
+    clients[1].send("move\n0 R1").await;
+    expect_move(
+        &mut clients,
+        "RRX",
+        &board(&[("Carol", 1000), ("Alice", 1000), ("Bob", 0)]),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn a_name_is_only_used_in_one_room_at_a_time() {
+    let server = TestServer::start(test_config(2)).await;
+    let _alice = server.join(0, "Alice").await;
+
+    let mut other = server.connect_to("/ws/other").await;
+    other
+        .expect("hello\n1\n2 lobby rounds resume error shutdown bombs stats")
+        .await;
+    other.send(&format!("olleh\n0\nAlice\n{COLOR}")).await;
+    let name = other.recv().await;
+    assert!(name.starts_with("name\nPlayer"), "{name}");
+}
+
+#[tokio::test]
+async fn asking_for_stats_quickly_keeps_the_room_going() {
+    let server = TestServer::start(test_config(2)).await;
+    let mut clients = server.start_game(&["A", "B"]).await;
+
+    for _ in 0..200 {
+        clients[0].send("stats").await;
+    }
+    // the game still answers new connections and moves
+    let mut newcomer = server.connect().await;
+    newcomer
+        .expect("hello\n1\n2 lobby rounds resume error shutdown bombs stats")
+        .await;
+    clients[0].send("move\n0 R1").await;
+    expect_move(&mut clients, "RX", &board(&[("A", 1000), ("B", 0)])).await;
+}
+
+#[tokio::test]
+async fn the_client_library_joins_and_resumes() {
+    let server = TestServer::start(GameConfig {
+        resume_grace_ms: 60000,
+        ..test_config(2)
+    })
+    .await;
+    let url = format!("ws://{}/ws", server.addr);
+
+    // a newer version and a capability the server doesn't know are left out of the offer
+    let mut capabilities = SERVER_CAPABILITIES.map(String::from).to_vec();
+    capabilities.push("teleport".to_string());
+    let alice = client::connect(
+        &url,
+        ClientOptions {
+            player_data: Some(("Alice".to_string(), COLOR.to_string())),
+            wire_format: WireFormat::Json,
+            protocol: (PROTOCOL_VERSION + 1, capabilities),
+            ..ClientOptions::default()
+        },
+    )
+    .await
+    .unwrap();
+    let session = alice.session.clone();
+    assert_eq!(session.player_name, "Alice");
+    assert_eq!(session.wire_format, WireFormat::Json);
+    assert_eq!(session.negotiated.version, PROTOCOL_VERSION);
+    assert_eq!(session.negotiated.capabilities, SERVER_CAPABILITIES);
+    let resume_token = session.resume_token.unwrap();
+
+    // the connection drops without a close frame, the seat waits for the resume token
+    drop(alice);
+    let resume = ClientOptions {
+        resume_token: Some(resume_token.clone()),
+        ..ClientOptions::default()
+    };
+    let mut alice = loop {
+        match client::connect(&url, resume.clone()).await {
+            // the server hasn't noticed the dropped connection yet
+            Err(ClientError::Rejected(ErrorCode::ResumeRejected, _)) => continue,
+            result => break result.unwrap(),
+        }
+    };
+    assert_eq!(alice.session.player_name, "Alice");
+    assert_eq!(alice.session.player_color, COLOR);
+    assert_eq!(alice.session.resume_token, Some(resume_token));
+
+    alice.request_stats().await.unwrap();
+    loop {
+        match alice.next_event().await.unwrap().unwrap() {
+            ServerPacket::PacketSTATS(stats) => {
+                assert_eq!(stats.len(), 1);
+                assert_eq!(stats[0].0, "Alice");
+                break;
+            }
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn the_client_library_talks_version_1() {
+    let server = TestServer::start(test_config(2)).await;
+    let bob = client::connect(
+        &format!("ws://{}/ws", server.addr),
+        ClientOptions {
+            protocol: (1, Vec::new()),
+            ..ClientOptions::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(bob.session.negotiated.version, 1);
+    assert!(bob.session.negotiated.capabilities.is_empty());
+    assert_eq!(bob.session.resume_token, None);
+    assert!(bob.session.player_name.starts_with("Player"));
+}